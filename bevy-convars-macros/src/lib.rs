@@ -0,0 +1,287 @@
+#![deny(missing_docs)]
+//! Derive macros for [bevy-convars](https://crates.io/crates/bevy-convars).
+//!
+//! Don't depend on this crate directly, use the re-exports provided by bevy-convars instead.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{ToTokens, quote, quote_spanned};
+use syn::{
+    Attribute, BinOp, Data, DeriveInput, Expr, Fields, LitStr, Member, Path, parse_macro_input,
+    spanned::Spanned,
+};
+
+/// Derives `CVarMeta` and the other traits a CVar needs. See the documentation of the re-export in bevy-convars.
+#[proc_macro_derive(CVar, attributes(cvar))]
+pub fn derive_cvar(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_cvar(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// The options provided through `#[cvar(...)]`.
+#[derive(Default)]
+struct CVarAttributes {
+    path: Option<LitStr>,
+    flags: Option<Expr>,
+    default: Option<Expr>,
+    description: Option<LitStr>,
+    aliases: Vec<LitStr>,
+    constraint: Option<Path>,
+    range: Option<Expr>,
+}
+
+impl CVarAttributes {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut out = Self::default();
+
+        for attr in attrs.iter().filter(|x| x.path().is_ident("cvar")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("path") {
+                    out.path = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("flags") {
+                    out.flags = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("default") {
+                    out.default = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("description") {
+                    out.description = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("alias") {
+                    out.aliases.push(meta.value()?.parse()?);
+                } else if meta.path.is_ident("constraint") {
+                    out.constraint = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("range") {
+                    out.range = Some(meta.value()?.parse()?);
+                } else {
+                    return Err(meta.error("Unknown cvar option."));
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(out)
+    }
+}
+
+/// Collects the doc comments of an item into a single string.
+fn doc_comments(attrs: &[Attribute]) -> String {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|x| x.path().is_ident("doc"))
+        .filter_map(|x| match &x.meta.require_name_value().ok()?.value {
+            Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(s),
+                ..
+            }) => Some(s.value()),
+            _ => None,
+        })
+        .map(|x| x.strip_prefix(' ').unwrap_or(&x).trim_end().to_owned())
+        .collect();
+
+    lines.join("\n").trim().to_owned()
+}
+
+/// Rewrites a flags expression so bare identifiers refer to the constants on `CVarFlags`.
+fn flags_expr(expr: Expr) -> TokenStream2 {
+    match expr {
+        Expr::Binary(binary) if matches!(binary.op, BinOp::BitOr(_)) => {
+            let left = flags_expr(*binary.left);
+            let right = flags_expr(*binary.right);
+
+            quote!(#left | #right)
+        }
+        Expr::Paren(paren) => {
+            let inner = flags_expr(*paren.expr);
+
+            quote!((#inner))
+        }
+        Expr::Path(path) if path.qself.is_none() && path.path.get_ident().is_some() => {
+            let ident = path.path.get_ident().unwrap();
+
+            quote!(::bevy_convars::CVarFlags::#ident)
+        }
+        other => other.into_token_stream(),
+    }
+}
+
+fn expand_cvar(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            input.generics.span(),
+            "CVars can't be generic.",
+        ));
+    }
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "CVars must be a struct with exactly one field.",
+        ));
+    };
+
+    let field = match &data.fields {
+        Fields::Named(fields) if fields.named.len() == 1 => &fields.named[0],
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => &fields.unnamed[0],
+        _ => {
+            return Err(syn::Error::new(
+                data.fields.span(),
+                "CVars must be a struct with exactly one field.",
+            ));
+        }
+    };
+
+    let member = match &field.ident {
+        Some(name) => Member::Named(name.clone()),
+        None => Member::Unnamed(0.into()),
+    };
+    let ty = &field.ty;
+
+    let attrs = CVarAttributes::parse(&input.attrs)?;
+
+    let Some(path) = attrs.path else {
+        return Err(syn::Error::new(
+            input.span(),
+            "CVars must have a path, i.e. #[cvar(path = \"a.b.c\")].",
+        ));
+    };
+
+    let flags = match attrs.flags {
+        Some(flags) => flags_expr(flags),
+        None => quote!(::bevy_convars::CVarFlags::LOCAL),
+    };
+
+    let description = match attrs.description {
+        Some(description) => description.value(),
+        None => doc_comments(&input.attrs),
+    };
+
+    let aliases = &attrs.aliases;
+
+    // Paths are checked in a const context, so invalid ones fail the build.
+    let path_checks = std::iter::once(&path)
+        .chain(aliases)
+        .map(|path| {
+            quote_spanned! {path.span()=>
+                const _: () = ::bevy_convars::path::assert_valid_cvar_path(#path);
+            }
+        })
+        .chain(std::iter::once(quote! {
+            // The path and aliases can't conflict with each other either.
+            const _: () = ::bevy_convars::path::assert_valid_cvar_paths(&[#path #(, #aliases)*]);
+        }));
+
+    let (default_impl, default_inner) = match attrs.default {
+        Some(default) => (
+            quote! {
+                impl ::core::default::Default for #ident {
+                    fn default() -> Self {
+                        Self { #member: #default }
+                    }
+                }
+            },
+            quote!(#default),
+        ),
+        None => (
+            quote!(),
+            quote!(<Self as ::core::default::Default>::default().#member),
+        ),
+    };
+
+    let (range_const, range_check) = attrs
+        .range
+        .map(|range| {
+            // i.e. `0.0..=1.0` rather than `0.0 ..= 1.0`.
+            let range_str = range.to_token_stream().to_string().replace(' ', "");
+
+            let range_const = quote! {
+                const RANGE: ::core::option::Option<&'static str> = ::core::option::Option::Some(#range_str);
+            };
+
+            let range_check = quote! {
+                if !::bevy_convars::reflect::range_contains::<#ty, _>(#range, value) {
+                    return ::core::result::Result::Err(::std::format!(
+                        "{:?} is outside of the allowed range {}",
+                        value,
+                        #range_str
+                    ));
+                }
+            };
+
+            (range_const, range_check)
+        })
+        .unzip();
+
+    let constraint_check = attrs.constraint.map(|constraint| {
+        quote! {
+            #constraint(value)?;
+        }
+    });
+
+    let validate = if range_check.is_some() || constraint_check.is_some() {
+        quote! {
+            fn validate(value: &#ty) -> ::core::result::Result<(), ::std::string::String> {
+                #range_check
+                #constraint_check
+                ::core::result::Result::Ok(())
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    Ok(quote! {
+        #(#path_checks)*
+
+        impl ::bevy_convars::reexports::bevy_ecs::prelude::Resource for #ident {}
+
+        impl ::core::ops::Deref for #ident {
+            type Target = #ty;
+
+            fn deref(&self) -> &Self::Target {
+                &self.#member
+            }
+        }
+
+        impl ::core::ops::DerefMut for #ident {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.#member
+            }
+        }
+
+        #default_impl
+
+        impl ::bevy_convars::reflect::CVarMeta for #ident {
+            type Inner = #ty;
+            const CVAR_PATH: &'static str = #path;
+            const DESCRIPTION: &'static str = #description;
+            const ALIASES: &'static [&'static str] = &[#(#aliases),*];
+            #range_const
+
+            fn flags() -> ::bevy_convars::CVarFlags {
+                #flags
+            }
+
+            fn default_inner() -> Self::Inner {
+                #default_inner
+            }
+
+            fn set_to_default(&mut self) {
+                self.#member = Self::default_inner();
+            }
+
+            #validate
+
+            fn register_value_type_data(registry: &mut ::bevy_convars::reexports::bevy_reflect::TypeRegistry) {
+                #[allow(unused_imports)]
+                use ::bevy_convars::reflect::{RegisterSerde as _, RegisterSerdeFallback as _, RegisterString as _, RegisterStringFallback as _};
+
+                (&::bevy_convars::reflect::TypeDataProbe::<#ty>::new()).register_serde(registry);
+                (&::bevy_convars::reflect::TypeDataProbe::<#ty>::new()).register_string(registry);
+            }
+        }
+    })
+}
@@ -12,6 +12,9 @@ cvar_collection! {
     pub struct CoreCVars & CoreCVarsMut {
         /// Enables logging ALL cvar modifications. This will log the change as info.
         log_cvar_changes = cvar LogCVarChanges("core.log_cvar_changes", CVarFlags::RUNTIME): bool = false,
+        /// Asset paths of the config layers to load at startup, relative to the loader's layer directory. Applied in order, so later layers take priority.
+        /// Only used when the [CVarLoaderPlugin](crate::loader::CVarLoaderPlugin) is configured to load asset layers.
+        config_layers = cvar ConfigLayers("core.config_layers", CVarFlags::LOCAL): Vec<String> = vec![],
     }
 
     /// Plugin that handles registering all the core CVars.
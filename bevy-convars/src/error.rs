@@ -17,6 +17,8 @@ pub enum CVarError {
     MissingCid,
     /// Error indicating the underlying type of the CVar cannot be deserialized, and as such cannot be reflected over.
     CannotDeserialize,
+    /// Error indicating the underlying type of the CVar cannot be serialized, either through reflection or as a string.
+    CannotSerialize,
     /// Error indicating the CVar failed to deserialize.
     FailedDeserialize(String),
     /// Error indicating applying a value to the CVar failed, containing the inner error.
@@ -24,6 +26,8 @@ pub enum CVarError {
         /// The inner error.
         inner: ApplyError,
     },
+    /// Error indicating the value was rejected by the CVar's constraints, containing the reason.
+    ConstraintViolation(String),
     /// Error indicating that the world could not fulfill the requested operation due to an access conflict with an ongoing operation.
     AccessConflict,
     #[cfg(feature = "parse_cvars")]
@@ -36,6 +40,30 @@ pub enum CVarError {
     /// An error when serializing TOML data.
     #[cfg(feature = "parse_cvars")]
     TomlSerError(toml_edit::ser::Error),
+
+    /// An error when converting a config to or from a format other than TOML, see [format](crate::format).
+    #[cfg(feature = "parse_cvars")]
+    FormatError(crate::format::FormatError),
+
+    /// An error within a `.cfg` script, see [script](crate::script).
+    #[cfg(feature = "parse_cvars")]
+    ScriptError(crate::script::ScriptError),
+
+    /// Error indicating a preset was never registered.
+    #[cfg(feature = "parse_cvars")]
+    UnknownPreset,
+
+    /// Error indicating a config contained keys that don't match any registered CVar, under [UnknownKeyPolicy::Error](crate::loader::UnknownKeyPolicy::Error).
+    #[cfg(feature = "config_loader")]
+    UnknownConfigKeys(Vec<crate::loader::UnknownKey>),
+
+    /// An error when loading or saving the user's config through a [CVarStorage](crate::storage::CVarStorage).
+    #[cfg(feature = "config_loader")]
+    StorageError(std::io::Error),
+
+    /// Error indicating a config asset was applied before it finished loading, or after it was unloaded.
+    #[cfg(feature = "config_loader_asset")]
+    AssetNotLoaded,
 }
 
 impl std::error::Error for CVarError {}
@@ -46,16 +74,22 @@ impl Display for CVarError {
             CVarError::UnknownCVar => write!(f, "Unknown CVar."),
             CVarError::BadCVarType => write!(
                 f,
-                "CVar is not internally a Tuple Struct of the expected layout, did you try to register it manually?"
+                "CVar is not of the expected type, did you try to register it manually?"
             ),
             CVarError::MissingCid => write!(f, "Missing ComponentID, was the resource registered?"),
             CVarError::CannotDeserialize => {
                 write!(f, "Underlying CVar type cannot be deserialized.")
             }
+            CVarError::CannotSerialize => {
+                write!(f, "Underlying CVar type cannot be serialized.")
+            }
             CVarError::FailedDeserialize(inner) => write!(f, "Failed to deserialize: {inner}"),
             CVarError::FailedApply { inner } => {
                 write!(f, "Failed to apply value to CVar. ({inner:?})")
             }
+            CVarError::ConstraintViolation(reason) => {
+                write!(f, "Value rejected by the CVar's constraints: {reason}")
+            }
             CVarError::AccessConflict => write!(
                 f,
                 "The requested operation conflicts with another ongoing operation on the world and cannot be performed."
@@ -67,6 +101,26 @@ impl Display for CVarError {
             }
             #[cfg(feature = "parse_cvars")]
             CVarError::TomlSerError(error) => write!(f, "TOML serializing error: {error}"),
+            #[cfg(feature = "parse_cvars")]
+            CVarError::FormatError(error) => write!(f, "Config format error: {error}"),
+            #[cfg(feature = "parse_cvars")]
+            CVarError::ScriptError(error) => write!(f, "Script error: {error}"),
+            #[cfg(feature = "parse_cvars")]
+            CVarError::UnknownPreset => write!(f, "Unknown preset."),
+            #[cfg(feature = "config_loader")]
+            CVarError::UnknownConfigKeys(keys) => {
+                write!(f, "Config contains unknown keys:")?;
+
+                for key in keys {
+                    write!(f, "\n{key}")?;
+                }
+
+                Ok(())
+            }
+            #[cfg(feature = "config_loader")]
+            CVarError::StorageError(error) => write!(f, "Config storage error: {error}"),
+            #[cfg(feature = "config_loader_asset")]
+            CVarError::AssetNotLoaded => write!(f, "The config asset isn't loaded."),
         }
     }
 }
@@ -91,6 +145,30 @@ impl From<toml_edit::ser::Error> for CVarError {
     }
 }
 
+#[cfg(feature = "parse_cvars")]
+impl From<crate::format::FormatError> for CVarError {
+    fn from(value: crate::format::FormatError) -> Self {
+        match value.downcast::<TomlError>() {
+            Ok(error) => Self::TomlError(*error),
+            Err(error) => Self::FormatError(error),
+        }
+    }
+}
+
+#[cfg(feature = "parse_cvars")]
+impl From<crate::script::ScriptError> for CVarError {
+    fn from(value: crate::script::ScriptError) -> Self {
+        Self::ScriptError(value)
+    }
+}
+
+#[cfg(feature = "config_loader")]
+impl From<std::io::Error> for CVarError {
+    fn from(value: std::io::Error) -> Self {
+        Self::StorageError(value)
+    }
+}
+
 impl From<ResourceFetchError> for CVarError {
     fn from(value: ResourceFetchError) -> Self {
         match value {
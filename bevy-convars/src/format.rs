@@ -0,0 +1,130 @@
+//! Provides the formats configs can be written in.
+//!
+//! Configs are always worked with as TOML documents internally, so formats other than [TomlFormat] are converted to and from TOML
+//! when loaded and saved. Those don't keep the comments and layout of the original text, and as such positions within them aren't reported.
+//!
+//! JSON and RON are provided behind the `format_json` and `format_ron` features respectively.
+//! See [ConfigLoader::with_format](crate::loader::ConfigLoader::with_format) and [CVarSaveContext::with_format](crate::save::CVarSaveContext::with_format).
+
+use std::fmt::Debug;
+
+use toml_edit::{DocumentMut, ImDocument};
+
+#[cfg(all(
+    test,
+    feature = "config_loader",
+    any(feature = "format_json", feature = "format_ron")
+))]
+mod tests;
+
+/// An error produced by a [ConfigFormat].
+pub type FormatError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A format configs can be loaded from and saved to.
+pub trait ConfigFormat: Default + Clone + Copy + Debug + Send + Sync + 'static {
+    /// The file extensions used by the format, without the leading `.`.
+    const EXTENSIONS: &'static [&'static str];
+
+    /// Whether parsed documents keep the text they were parsed from, so positions within them point into the original text.
+    const SPANS: bool;
+
+    /// Parses text in this format into a TOML document.
+    fn parse(text: String) -> Result<ImDocument<String>, FormatError>;
+
+    /// Serializes a TOML document into text in this format.
+    fn serialize(document: &DocumentMut) -> Result<String, FormatError>;
+}
+
+/// The TOML format, used by default. Comments and layout are preserved when saving.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct TomlFormat;
+
+impl ConfigFormat for TomlFormat {
+    const EXTENSIONS: &'static [&'static str] = &["toml"];
+
+    const SPANS: bool = true;
+
+    fn parse(text: String) -> Result<ImDocument<String>, FormatError> {
+        Ok(ImDocument::parse(text)?)
+    }
+
+    fn serialize(document: &DocumentMut) -> Result<String, FormatError> {
+        Ok(document.to_string())
+    }
+}
+
+/// The JSON format. `null` isn't supported, as TOML has no equivalent.
+#[cfg(feature = "format_json")]
+#[derive(Default, Clone, Copy, Debug)]
+pub struct JsonFormat;
+
+#[cfg(feature = "format_json")]
+impl ConfigFormat for JsonFormat {
+    const EXTENSIONS: &'static [&'static str] = &["json"];
+
+    const SPANS: bool = false;
+
+    fn parse(text: String) -> Result<ImDocument<String>, FormatError> {
+        to_toml(&serde_json::from_str::<serde_json::Value>(&text)?)
+    }
+
+    fn serialize(document: &DocumentMut) -> Result<String, FormatError> {
+        let value: serde_json::Value = from_toml(document)?;
+
+        Ok(serde_json::to_string_pretty(&value)?)
+    }
+}
+
+/// The RON format. Structs are written as maps (i.e. `{ "render": { "ssao": true } }`) when saving, while either form is accepted when loading.
+/// Units aren't supported, as TOML has no equivalent.
+#[cfg(feature = "format_ron")]
+#[derive(Default, Clone, Copy, Debug)]
+pub struct RonFormat;
+
+#[cfg(feature = "format_ron")]
+impl ConfigFormat for RonFormat {
+    const EXTENSIONS: &'static [&'static str] = &["ron"];
+
+    const SPANS: bool = false;
+
+    fn parse(text: String) -> Result<ImDocument<String>, FormatError> {
+        to_toml(&ron::from_str::<ron::Value>(&text)?)
+    }
+
+    fn serialize(document: &DocumentMut) -> Result<String, FormatError> {
+        let value: ron::Value = from_toml(document)?;
+
+        Ok(ron::ser::to_string_pretty(
+            &value,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+}
+
+/// Converts a value from another format into a TOML document.
+#[cfg(any(feature = "format_json", feature = "format_ron"))]
+fn to_toml(value: &impl serde::Serialize) -> Result<ImDocument<String>, FormatError> {
+    /// Expands inline tables into standard ones, as groups of CVars are only looked for in standard tables.
+    fn expand(table: &mut toml_edit::Table) {
+        for (_, item) in table.iter_mut() {
+            if let Some(inline) = item.as_inline_table_mut() {
+                *item = toml_edit::Item::Table(std::mem::take(inline).into_table());
+            }
+
+            if let Some(table) = item.as_table_mut() {
+                expand(table);
+            }
+        }
+    }
+
+    let mut document = toml_edit::ser::to_document(value)?;
+    expand(document.as_table_mut());
+
+    Ok(ImDocument::parse(document.to_string())?)
+}
+
+/// Converts a TOML document into a value of another format.
+#[cfg(any(feature = "format_json", feature = "format_ron"))]
+fn from_toml<T: serde::de::DeserializeOwned>(document: &DocumentMut) -> Result<T, FormatError> {
+    Ok(toml_edit::de::from_document(document.clone())?)
+}
@@ -0,0 +1,88 @@
+use std::error::Error;
+
+use crate::{
+    loader::ConfigLoader,
+    save::CVarSaveContext,
+    tests::{TestArray, TestInteger, TestVolume, make_test_app},
+};
+
+#[cfg(feature = "format_json")]
+#[test]
+pub fn load_and_save_json() -> Result<(), Box<dyn Error>> {
+    use super::JsonFormat;
+
+    let mut app = make_test_app();
+
+    let document = r#"{ "testrig": { "test_int": 7, "old_volume": 0.25, "test_array": [1, 2] } }"#;
+
+    let report = ConfigLoader::default()
+        .with_format::<JsonFormat>()
+        .apply_from_string(app.world_mut(), document, Some("config.json"), false)?;
+
+    assert_eq!(report.applied.len(), 3);
+    // Positions within the generated TOML don't mean anything to the user.
+    assert!(report.applied.iter().all(|x| x.location.is_none()));
+
+    assert_eq!(**app.world().resource::<TestInteger>(), 7);
+    assert_eq!(**app.world().resource::<TestVolume>(), 0.25);
+    assert_eq!(**app.world().resource::<TestArray>(), [1, 2]);
+
+    let mut save_ctx = CVarSaveContext::blank().with_format::<JsonFormat>();
+    save_ctx.save_world(app.world())?;
+
+    let saved: serde_json::Value = serde_json::from_str(&save_ctx.serialize()?)?;
+
+    assert_eq!(saved["testrig"]["test_int"], 7);
+    assert_eq!(saved["testrig"]["test_array"], serde_json::json!([1, 2]));
+    assert_eq!(saved["testrig"]["derived"]["volume"], 0.25);
+
+    Ok(())
+}
+
+#[cfg(feature = "format_ron")]
+#[test]
+pub fn load_and_save_ron() -> Result<(), Box<dyn Error>> {
+    use super::RonFormat;
+
+    let mut app = make_test_app();
+
+    // Both struct and map syntax are accepted.
+    let document = r#"(testrig: (test_int: 3, derived: { "volume": 0.75 }))"#;
+
+    ConfigLoader::default()
+        .with_format::<RonFormat>()
+        .apply_from_string(app.world_mut(), document, Some("config.ron"), false)?;
+
+    assert_eq!(**app.world().resource::<TestInteger>(), 3);
+    assert_eq!(**app.world().resource::<TestVolume>(), 0.75);
+
+    let mut save_ctx = CVarSaveContext::blank().with_format::<RonFormat>();
+    save_ctx.save_world(app.world())?;
+
+    // Saved configs load back to the same values.
+    let mut reloaded = make_test_app();
+
+    ConfigLoader::default()
+        .with_format::<RonFormat>()
+        .apply_from_string(reloaded.world_mut(), &save_ctx.serialize()?, None, false)?;
+
+    assert_eq!(**reloaded.world().resource::<TestInteger>(), 3);
+    assert_eq!(**reloaded.world().resource::<TestVolume>(), 0.75);
+
+    Ok(())
+}
+
+#[cfg(feature = "format_json")]
+#[test]
+pub fn reject_malformed_json() {
+    use super::JsonFormat;
+    use crate::CVarError;
+
+    let mut app = make_test_app();
+
+    let res = ConfigLoader::default()
+        .with_format::<JsonFormat>()
+        .apply_from_string(app.world_mut(), r#"{ "testrig": null }"#, None, false);
+
+    assert!(matches!(res, Err(CVarError::FormatError(_))));
+}
@@ -0,0 +1,608 @@
+//! Provides the layer stack CVar values are resolved from.
+//!
+//! Every source of configuration (embedded layers, asset layers, the user's config, the command line, ...) is kept as a [ConfigLayer]
+//! within the [LayerStack] of [CVarManagement], ordered by priority. A CVar's effective value is that of the highest priority layer
+//! containing it, or its default value if no layer does.
+//!
+//! Adding, removing, replacing or reordering a layer (see [WorldExtensions](crate::WorldExtensions)) recomputes the CVars it contains.
+//!
+//! # Remarks
+//! Modifying a CVar's resource directly happens outside of the stack, and will be overwritten if a layer containing that CVar changes.
+//! Use a [LayerKind::Runtime] layer for runtime changes that should take part in resolution.
+
+use std::{fmt::Display, str::FromStr};
+
+use bevy_ecs::{
+    change_detection::{DetectChanges, DetectChangesMut},
+    reflect::ReflectResource,
+    world::{Mut, World},
+};
+use bevy_log::warn;
+use bevy_platform::collections::HashMap;
+use bevy_reflect::std_traits::ReflectDefault;
+use serde::de::IntoDeserializer;
+use toml_edit::Value;
+
+use crate::{CVarError, CVarManagement, parse::CVarOverride, reflect::ReflectCVar};
+
+#[cfg(test)]
+mod tests;
+
+/// The kind of a config layer. New layers are placed above every layer of the same or a lower kind,
+/// so the variants are listed from lowest to highest priority.
+///
+/// Every CVar's default value sits beneath all layers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LayerKind {
+    /// Layers embedded into or provided by the application, i.e. through [CVarLoaderPluginBuilder::add_layer](crate::loader::CVarLoaderPluginBuilder::add_layer).
+    Embedded,
+    /// Layers loaded as assets.
+    Asset,
+    /// The user's config file.
+    User,
+    /// Scripts run at startup, i.e. [AUTOEXEC](crate::script::AUTOEXEC).
+    Script,
+    /// Values provided by environment variables, see [ConfigLayer::from_env].
+    Environment,
+    /// Values provided on the command line, see [ConfigLayer::from_overrides].
+    CommandLine,
+    /// Values set at runtime, i.e. by a console.
+    Runtime,
+}
+
+impl LayerKind {
+    /// Whether values from this kind of layer are applied without triggering change detection,
+    /// so they look like the CVar's default value and aren't saved to the user's config.
+    pub fn bypasses_change_detection(&self) -> bool {
+        matches!(
+            self,
+            LayerKind::Script | LayerKind::Environment | LayerKind::CommandLine
+        )
+    }
+}
+
+/// Identifies a layer within the [LayerStack].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LayerId(u64);
+
+/// Where a value was written within its layer's source, as 1-based line and column numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SourceLocation {
+    /// The line the value is on.
+    pub line: usize,
+    /// The column the value starts at, in characters.
+    pub column: usize,
+}
+
+impl SourceLocation {
+    /// Finds the location of the given byte offset within a source.
+    pub fn from_offset(source: &str, offset: usize) -> Self {
+        let before = &source[..offset.min(source.len())];
+        let line_start = before.rfind('\n').map(|x| x + 1).unwrap_or(0);
+
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A set of CVar values from a single source.
+#[derive(Debug, Clone)]
+pub struct ConfigLayer {
+    kind: LayerKind,
+    source: String,
+    values: HashMap<String, Value>,
+    locations: HashMap<String, SourceLocation>,
+}
+
+impl ConfigLayer {
+    /// Creates a new, empty layer.
+    pub fn new(kind: LayerKind, source: impl Into<String>) -> Self {
+        Self {
+            kind,
+            source: source.into(),
+            values: HashMap::default(),
+            locations: HashMap::default(),
+        }
+    }
+
+    /// Creates a layer from a set of CVar overrides, i.e. those provided on the command line.
+    pub fn from_overrides<'a>(
+        kind: LayerKind,
+        source: impl Into<String>,
+        overrides: impl IntoIterator<Item = &'a CVarOverride>,
+    ) -> Self {
+        let mut layer = Self::new(kind, source);
+
+        for r#override in overrides {
+            layer.insert(r#override.0.clone(), r#override.1.clone());
+        }
+
+        layer
+    }
+
+    /// Creates a [LayerKind::Environment] layer from the environment variables matching registered CVars.
+    /// # Remarks
+    /// The variable for a CVar is its path prefixed with `prefix`, in uppercase and with `.` and `-` replaced by `__` and `_`.
+    /// For example, `render.max-fps` with the prefix `GAME_` is read from `GAME_RENDER__MAX_FPS`.
+    ///
+    /// Values are parsed as TOML, falling back to a plain string if they aren't valid TOML.
+    pub fn from_env(management: &CVarManagement, prefix: &str) -> Self {
+        let mut layer = Self::new(LayerKind::Environment, "environment");
+
+        for reg in management.iterate_cvar_types() {
+            let cvar = reg.data::<ReflectCVar>().expect("Impossible.");
+
+            for path in std::iter::once(cvar.cvar_path()).chain(cvar.aliases().iter().copied()) {
+                let name = format!(
+                    "{prefix}{}",
+                    path.replace('.', "__")
+                        .replace('-', "_")
+                        .to_ascii_uppercase()
+                );
+
+                if let Ok(value) = std::env::var(&name) {
+                    let value =
+                        Value::from_str(&value).unwrap_or_else(|_| Value::from(value.trim()));
+
+                    layer.insert(cvar.cvar_path().to_owned(), value);
+                }
+            }
+        }
+
+        layer
+    }
+
+    /// Returns the kind of this layer.
+    pub fn kind(&self) -> LayerKind {
+        self.kind
+    }
+
+    /// Returns the source of this layer, i.e. the file it was loaded from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns the value this layer has for the given CVar, if any.
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        self.values.get(path)
+    }
+
+    /// Returns where the value this layer has for the given CVar was written, if known.
+    pub fn location(&self, path: &str) -> Option<SourceLocation> {
+        self.locations.get(path).copied()
+    }
+
+    /// Sets the value this layer has for the given CVar.
+    pub fn insert(&mut self, path: impl Into<String>, value: Value) {
+        let path = path.into();
+
+        self.locations.remove(&path);
+        self.values.insert(path, value);
+    }
+
+    /// Sets the value this layer has for the given CVar, along with where it was written.
+    pub fn insert_with_location(
+        &mut self,
+        path: impl Into<String>,
+        value: Value,
+        location: SourceLocation,
+    ) {
+        let path = path.into();
+
+        self.locations.insert(path.clone(), location);
+        self.values.insert(path, value);
+    }
+
+    /// Removes the value this layer has for the given CVar.
+    pub fn remove(&mut self, path: &str) -> Option<Value> {
+        self.locations.remove(path);
+        self.values.remove(path)
+    }
+
+    /// Moves the value under `from` to `to`, unless the layer already has a value for `to`.
+    fn rename(&mut self, from: &str, to: &str) {
+        let location = self.locations.remove(from);
+
+        let Some(value) = self.values.remove(from) else {
+            return;
+        };
+
+        if self.values.contains_key(to) {
+            return;
+        }
+
+        self.values.insert(to.to_owned(), value);
+
+        if let Some(location) = location {
+            self.locations.insert(to.to_owned(), location);
+        }
+    }
+
+    /// Returns an iterator over every CVar path and value in this layer.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Returns the number of values in this layer.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns whether this layer has no values.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// An ordered stack of [ConfigLayer]s, from lowest to highest priority.
+#[derive(Default)]
+pub struct LayerStack {
+    layers: Vec<(LayerId, ConfigLayer)>,
+    next_id: u64,
+    /// The layer and value each CVar was last resolved to, so unchanged CVars aren't touched.
+    applied: HashMap<String, (LayerId, Value)>,
+}
+
+impl LayerStack {
+    /// Returns every layer, from lowest to highest priority.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (LayerId, &ConfigLayer)> {
+        self.layers.iter().map(|(id, layer)| (*id, layer))
+    }
+
+    /// Returns the layer with the given ID.
+    pub fn get(&self, id: LayerId) -> Option<&ConfigLayer> {
+        self.layers.iter().find(|x| x.0 == id).map(|x| &x.1)
+    }
+
+    /// Returns the position of the layer with the given ID, where 0 is the lowest priority.
+    pub fn position(&self, id: LayerId) -> Option<usize> {
+        self.layers.iter().position(|x| x.0 == id)
+    }
+
+    /// Returns the highest priority layer containing the given CVar, along with its value.
+    /// `None` means the CVar resolves to its default value.
+    pub fn effective_value(&self, path: &str) -> Option<(LayerId, &ConfigLayer, &Value)> {
+        self.layers
+            .iter()
+            .rev()
+            .find_map(|(id, layer)| layer.get(path).map(|value| (*id, layer, value)))
+    }
+
+    /// Inserts a layer above every layer of the same or a lower kind.
+    pub(crate) fn insert(&mut self, layer: ConfigLayer) -> LayerId {
+        let id = LayerId(self.next_id);
+        self.next_id += 1;
+
+        let index = self
+            .layers
+            .iter()
+            .position(|(_, x)| x.kind > layer.kind)
+            .unwrap_or(self.layers.len());
+
+        self.layers.insert(index, (id, layer));
+
+        id
+    }
+
+    pub(crate) fn remove(&mut self, id: LayerId) -> Option<ConfigLayer> {
+        let index = self.position(id)?;
+
+        Some(self.layers.remove(index).1)
+    }
+
+    pub(crate) fn get_mut(&mut self, id: LayerId) -> Option<&mut ConfigLayer> {
+        self.layers.iter_mut().find(|x| x.0 == id).map(|x| &mut x.1)
+    }
+
+    pub(crate) fn move_to(&mut self, id: LayerId, index: usize) -> bool {
+        let Some(layer) = self.position(id).map(|x| self.layers.remove(x)) else {
+            return false;
+        };
+
+        let index = index.min(self.layers.len());
+        self.layers.insert(index, layer);
+
+        true
+    }
+
+    /// Rewrites any keys using one of the CVar's aliases to its canonical path.
+    pub(crate) fn canonicalize(&mut self, path: &str, aliases: &[&str]) {
+        for (_, layer) in self.layers.iter_mut() {
+            for alias in aliases {
+                layer.rename(alias, path);
+            }
+        }
+    }
+}
+
+/// A layer's value for a CVar, as returned by [CVarManagement::explain_cvar].
+#[derive(Debug, Clone)]
+pub struct LayerEntry {
+    /// The layer containing the value.
+    pub layer: LayerId,
+    /// The kind of the layer.
+    pub kind: LayerKind,
+    /// The source of the layer, see [ConfigLayer::source].
+    pub source: String,
+    /// The value the layer has for the CVar.
+    pub value: Value,
+    /// Where the value was written within the source, if known.
+    pub location: Option<SourceLocation>,
+    /// Whether this is the value the CVar resolved to.
+    pub effective: bool,
+}
+
+impl Display for LayerEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)?;
+
+        if let Some(location) = self.location {
+            write!(f, ":{location}")?;
+        }
+
+        write!(f, " ({:?}) = {}", self.kind, self.value.to_string().trim())?;
+
+        if self.effective {
+            write!(f, " (effective)")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl CVarManagement {
+    /// Explains why a CVar has the value it does, returning every layer that contains it from highest to lowest priority.
+    /// The entry of the layer the CVar's current value was applied from is marked as [LayerEntry::effective]. This is usually the first one,
+    /// unless its value was rejected (i.e. for being out of range), in which case none or a lower one is marked. An empty list means the CVar resolves to its default value.
+    ///
+    /// Aliases are resolved to the CVar they belong to.
+    /// # Remarks
+    /// Values set by modifying the CVar's resource directly aren't part of the stack, and as such aren't listed.
+    pub fn explain_cvar(&self, path: &str) -> Result<Vec<LayerEntry>, CVarError> {
+        let path = canonical_path(self, path).ok_or(CVarError::UnknownCVar)?;
+
+        let applied = self.layers.applied.get(path).map(|(id, _)| *id);
+
+        let entries = self
+            .layers
+            .iter()
+            .rev()
+            .filter_map(|(id, layer)| {
+                layer.get(path).map(|value| LayerEntry {
+                    layer: id,
+                    kind: layer.kind(),
+                    source: layer.source().to_owned(),
+                    value: value.clone(),
+                    location: layer.location(path),
+                    effective: applied == Some(id),
+                })
+            })
+            .collect();
+
+        Ok(entries)
+    }
+}
+
+/// Compares two values, ignoring their surrounding whitespace and comments.
+pub(crate) fn same_value(left: &Value, right: &Value) -> bool {
+    let (mut left, mut right) = (left.clone(), right.clone());
+
+    left.decor_mut().clear();
+    right.decor_mut().clear();
+
+    left.to_string() == right.to_string()
+}
+
+/// Resets a CVar to its default value, marking it as being the default again without triggering change detection,
+/// so it isn't saved to the user's config (see [IsDefault](crate::defaults::IsDefault)).
+fn reset_to_default(
+    world: &mut World,
+    management: &CVarManagement,
+    path: &str,
+) -> Result<(), CVarError> {
+    let cid = management.tree.get(path).ok_or(CVarError::UnknownCVar)?;
+    let registration = management
+        .resources
+        .get(&cid)
+        .ok_or(CVarError::MissingCid)?;
+
+    let reflect_cvar = registration
+        .data::<ReflectCVar>()
+        .ok_or(CVarError::BadCVarType)?;
+    let default = registration
+        .data::<ReflectDefault>()
+        .ok_or(CVarError::BadCVarType)?
+        .default();
+
+    let inner = reflect_cvar
+        .reflect_inner(default.as_partial_reflect())?
+        .try_as_reflect()
+        .ok_or(CVarError::BadCVarType)?;
+
+    management.set_cvar_reflect_no_change(world, path, inner)?;
+
+    let mut cvar = registration
+        .data::<ReflectResource>()
+        .ok_or(CVarError::BadCVarType)?
+        .reflect_mut(world)?;
+    let last_changed = cvar.last_changed();
+    cvar.set_last_added(last_changed);
+
+    Ok(())
+}
+
+/// Recomputes the effective value of the given CVars from the layer stack, applying those that changed.
+/// Returns the CVars that failed to apply.
+/// # Remarks
+/// CVars that aren't registered are skipped, they're resolved once they're registered.
+pub(crate) fn recompute_cvars(
+    world: &mut World,
+    paths: impl IntoIterator<Item = String>,
+) -> Vec<(String, CVarError)> {
+    let mut failures = vec![];
+
+    world.resource_scope(|world, mut management: Mut<CVarManagement>| {
+        for path in paths {
+            if management.tree.get(&path).is_none() {
+                continue;
+            }
+
+            let winner = management
+                .layers
+                .effective_value(&path)
+                .map(|(id, layer, value)| {
+                    (id, layer.kind(), layer.source().to_owned(), value.clone())
+                });
+
+            let res = match winner {
+                Some((id, kind, source, value)) => {
+                    let unchanged = management
+                        .layers
+                        .applied
+                        .get(&path)
+                        .is_some_and(|(applied, old)| *applied == id && same_value(old, &value));
+
+                    if unchanged {
+                        continue;
+                    }
+
+                    let deserializer = IntoDeserializer::into_deserializer(value.clone());
+
+                    let res = if kind.bypasses_change_detection() {
+                        management.set_cvar_deserialize_no_change(world, &path, deserializer)
+                    } else {
+                        management.set_cvar_deserialize(world, &path, deserializer)
+                    };
+
+                    match res {
+                        Ok(()) => {
+                            management.layers.applied.insert(path.clone(), (id, value));
+                            Ok(())
+                        }
+                        Err(e) => {
+                            warn!("Failed to apply {path} from {source}, got error: {e}");
+                            Err(e)
+                        }
+                    }
+                }
+                None => {
+                    if management.layers.applied.remove(&path).is_none() {
+                        continue;
+                    }
+
+                    reset_to_default(world, &management, &path)
+                }
+            };
+
+            if let Err(e) = res {
+                failures.push((path, e));
+            }
+        }
+    });
+
+    failures
+}
+
+/// Returns the canonical path of a registered CVar, resolving aliases.
+pub(crate) fn canonical_path(management: &CVarManagement, path: &str) -> Option<&'static str> {
+    let cid = management.tree.get(path)?;
+
+    management
+        .resources
+        .get(&cid)?
+        .data::<ReflectCVar>()
+        .map(|x| x.cvar_path())
+}
+
+/// Rewrites any keys of the layer using one of a registered CVar's aliases to its canonical path.
+fn canonicalize_layer(management: &CVarManagement, layer: &mut ConfigLayer) {
+    let aliased: Vec<(String, &'static str)> = layer
+        .iter()
+        .filter_map(|(path, _)| {
+            canonical_path(management, path)
+                .filter(|x| *x != path)
+                .map(|x| (path.to_owned(), x))
+        })
+        .collect();
+
+    for (alias, path) in aliased {
+        layer.rename(&alias, path);
+    }
+}
+
+/// Adds a layer to the stack, recomputing the CVars it contains.
+pub(crate) fn add_layer(
+    world: &mut World,
+    mut layer: ConfigLayer,
+) -> (LayerId, Vec<(String, CVarError)>) {
+    let mut management = world.resource_mut::<CVarManagement>();
+
+    canonicalize_layer(&management, &mut layer);
+
+    let paths: Vec<String> = layer.values.keys().cloned().collect();
+    let id = management.layers.insert(layer);
+
+    (id, recompute_cvars(world, paths))
+}
+
+/// Removes a layer from the stack, recomputing the CVars it contained.
+pub(crate) fn remove_layer(world: &mut World, id: LayerId) -> Option<ConfigLayer> {
+    let layer = world.resource_mut::<CVarManagement>().layers.remove(id)?;
+
+    recompute_cvars(world, layer.values.keys().cloned());
+
+    Some(layer)
+}
+
+/// Replaces the contents of a layer, recomputing the CVars either version contains.
+pub(crate) fn replace_layer(
+    world: &mut World,
+    id: LayerId,
+    mut layer: ConfigLayer,
+) -> Option<Vec<(String, CVarError)>> {
+    let mut management = world.resource_mut::<CVarManagement>();
+
+    canonicalize_layer(&management, &mut layer);
+
+    let existing = management.layers.get_mut(id)?;
+    let old = std::mem::replace(existing, layer);
+
+    let mut paths: Vec<String> = old.values.into_keys().collect();
+    paths.extend(
+        existing
+            .values
+            .keys()
+            .filter(|x| !paths.contains(x))
+            .cloned()
+            .collect::<Vec<_>>(),
+    );
+
+    Some(recompute_cvars(world, paths))
+}
+
+/// Moves a layer to the given position in the stack, recomputing the CVars it contains.
+pub(crate) fn move_layer(world: &mut World, id: LayerId, index: usize) -> bool {
+    let mut management = world.resource_mut::<CVarManagement>();
+
+    if !management.layers.move_to(id, index) {
+        return false;
+    }
+
+    let paths: Vec<String> = management
+        .layers
+        .get(id)
+        .map(|x| x.values.keys().cloned().collect())
+        .unwrap_or_default();
+
+    recompute_cvars(world, paths);
+
+    true
+}
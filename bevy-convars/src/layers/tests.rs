@@ -0,0 +1,247 @@
+use std::{collections::BTreeMap, error::Error};
+
+use crate::{
+    CVarManagement, WorldExtensions,
+    defaults::IsDefault,
+    parse::CVarOverride,
+    save::CVarSaveContext,
+    tests::{TestArray, TestBool, TestInteger, TestMap, TestVolume, make_test_app},
+};
+
+use super::{ConfigLayer, LayerKind};
+
+fn layer(kind: LayerKind, source: &str, values: &[(&str, i64)]) -> ConfigLayer {
+    let mut layer = ConfigLayer::new(kind, source);
+
+    for (path, value) in values {
+        layer.insert(*path, (*value).into());
+    }
+
+    layer
+}
+
+#[test]
+pub fn resolve_by_priority() {
+    let mut app = make_test_app();
+    let world = app.world_mut();
+
+    // Added out of order, the kind decides the priority.
+    world.add_cvar_layer(layer(LayerKind::User, "user", &[("testrig.test_int", 3)]));
+    world.add_cvar_layer(layer(
+        LayerKind::Embedded,
+        "embedded",
+        &[("testrig.test_int", 1)],
+    ));
+
+    assert_eq!(**world.resource::<TestInteger>(), 3);
+
+    let management = world.resource::<CVarManagement>();
+    let (_, winner, _) = management
+        .layers()
+        .effective_value("testrig.test_int")
+        .unwrap();
+
+    assert_eq!(winner.source(), "user");
+    assert_eq!(
+        management
+            .layers()
+            .iter()
+            .map(|(_, x)| x.kind())
+            .collect::<Vec<_>>(),
+        [LayerKind::Embedded, LayerKind::User]
+    );
+}
+
+#[test]
+pub fn remove_and_reorder_layers() {
+    let mut app = make_test_app();
+    let world = app.world_mut();
+
+    let default = **world.resource::<TestInteger>();
+
+    let embedded = world.add_cvar_layer(layer(
+        LayerKind::Embedded,
+        "embedded",
+        &[("testrig.test_int", 1)],
+    ));
+    let user = world.add_cvar_layer(layer(LayerKind::User, "user", &[("testrig.test_int", 3)]));
+
+    // Moving the embedded layer to the top makes it win.
+    assert!(world.move_cvar_layer(embedded, 1));
+    assert_eq!(**world.resource::<TestInteger>(), 1);
+
+    // Removing it falls back to the next layer down.
+    world.remove_cvar_layer(embedded);
+    assert_eq!(**world.resource::<TestInteger>(), 3);
+
+    // And with no layers left, the default.
+    world.remove_cvar_layer(user);
+    assert_eq!(**world.resource::<TestInteger>(), default);
+}
+
+#[test]
+pub fn fall_back_replaces_collections() -> Result<(), Box<dyn Error>> {
+    let mut app = make_test_app();
+    let world = app.world_mut();
+
+    let mut embedded = ConfigLayer::new(LayerKind::Embedded, "embedded");
+    embedded.insert("testrig.test_array", "[9]".parse()?);
+    embedded.insert("testrig.test_map", "{ a = 1 }".parse()?);
+    world.add_cvar_layer(embedded);
+
+    let mut console = ConfigLayer::new(LayerKind::Runtime, "console");
+    console.insert("testrig.test_array", "[1, 2, 3]".parse()?);
+    console.insert("testrig.test_map", "{ b = 2, c = 3 }".parse()?);
+    let console = world.add_cvar_layer(console);
+
+    assert_eq!(**world.resource::<TestArray>(), [1, 2, 3]);
+    assert_eq!(
+        **world.resource::<TestMap>(),
+        BTreeMap::from([("b".to_owned(), 2), ("c".to_owned(), 3)])
+    );
+
+    // The lower layer's values replace the collections rather than being merged into them.
+    world.remove_cvar_layer(console);
+
+    assert_eq!(**world.resource::<TestArray>(), [9]);
+    assert_eq!(
+        **world.resource::<TestMap>(),
+        BTreeMap::from([("a".to_owned(), 1)])
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn reset_cvars_look_default() -> Result<(), Box<dyn Error>> {
+    let mut app = make_test_app();
+    let world = app.world_mut();
+
+    let user = world.add_cvar_layer(layer(LayerKind::User, "user", &[("testrig.test_int", 3)]));
+    let mut balance = layer(LayerKind::Asset, "balance.toml", &[]);
+    balance.insert("testrig.test_bool", false.into());
+    let asset = world.add_cvar_layer(balance);
+
+    assert!(!world.resource_ref::<TestInteger>().is_default());
+    assert!(!world.resource_ref::<TestBool>().is_default());
+
+    // Falling back to the default, whether the layer is removed or replaced, doesn't count as a change.
+    world.remove_cvar_layer(user);
+    world.replace_cvar_layer(asset, layer(LayerKind::Asset, "balance.toml", &[]));
+
+    assert!(world.resource_ref::<TestInteger>().is_default());
+    assert!(world.resource_ref::<TestBool>().is_default());
+
+    let mut save_ctx = CVarSaveContext::blank();
+    save_ctx.save_world(world)?;
+
+    assert_eq!(save_ctx.to_string(), "");
+
+    Ok(())
+}
+
+#[test]
+pub fn replace_layer_only_touches_changed_cvars() {
+    let mut app = make_test_app();
+    let world = app.world_mut();
+
+    let id = world.add_cvar_layer(layer(
+        LayerKind::Asset,
+        "balance.toml",
+        &[("testrig.test_int", 7)],
+    ));
+
+    let mut replacement = layer(LayerKind::Asset, "balance.toml", &[("testrig.test_int", 7)]);
+    replacement.insert("testrig.test_bool", false.into());
+
+    // A runtime change outside of the stack is kept, as the layer's value for it didn't change.
+    **world.resource_mut::<TestInteger>() = 42;
+
+    assert!(world.replace_cvar_layer(id, replacement));
+
+    assert_eq!(**world.resource::<TestInteger>(), 42);
+    assert!(!**world.resource::<TestBool>());
+}
+
+#[test]
+pub fn replace_layer_resolves_aliases() -> Result<(), Box<dyn Error>> {
+    let mut app = make_test_app();
+    let world = app.world_mut();
+
+    let id = world.add_cvar_layer(ConfigLayer::new(LayerKind::Runtime, "console"));
+
+    let mut replacement = ConfigLayer::new(LayerKind::Runtime, "console");
+    replacement.insert("testrig.old_volume", 0.25.into());
+
+    assert!(world.replace_cvar_layer(id, replacement));
+
+    assert_eq!(**world.resource::<TestVolume>(), 0.25);
+
+    let explained = world
+        .resource::<CVarManagement>()
+        .explain_cvar("testrig.derived.volume")?;
+
+    assert_eq!(explained.len(), 1);
+    assert_eq!(explained[0].source, "console");
+
+    Ok(())
+}
+
+#[test]
+pub fn explain_rejected_layer() -> Result<(), Box<dyn Error>> {
+    let mut app = make_test_app();
+    let world = app.world_mut();
+
+    let mut user = ConfigLayer::new(LayerKind::User, "user.toml");
+    user.insert("testrig.derived.volume", 0.25.into());
+    world.add_cvar_layer(user);
+
+    // Out of range, so the CVar keeps the user's value.
+    let mut console = ConfigLayer::new(LayerKind::Runtime, "console");
+    console.insert("testrig.derived.volume", 2.0.into());
+    world.add_cvar_layer(console);
+
+    assert_eq!(**world.resource::<TestVolume>(), 0.25);
+
+    let explained = world
+        .resource::<CVarManagement>()
+        .explain_cvar("testrig.derived.volume")?;
+
+    assert_eq!(
+        explained
+            .iter()
+            .map(|x| (x.source.as_str(), x.effective))
+            .collect::<Vec<_>>(),
+        [("console", false), ("user.toml", true)]
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn command_line_layer() -> Result<(), Box<dyn Error>> {
+    let mut app = make_test_app();
+    let world = app.world_mut();
+
+    let overrides: Vec<CVarOverride> = vec![
+        "testrig.test_int=12".parse()?,
+        "testrig.old_volume=0.125".parse()?,
+    ];
+
+    world.add_cvar_layer(layer(
+        LayerKind::Runtime,
+        "console",
+        &[("testrig.test_int", 99)],
+    ));
+    world.add_cvar_layer(ConfigLayer::from_overrides(
+        LayerKind::CommandLine,
+        "command line",
+        &overrides,
+    ));
+
+    // Runtime layers outrank the command line, and aliases resolve to the CVar they belong to.
+    assert_eq!(**world.resource::<TestInteger>(), 99);
+    assert_eq!(**world.resource::<TestVolume>(), 0.125);
+
+    Ok(())
+}
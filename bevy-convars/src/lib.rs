@@ -12,15 +12,18 @@ use bevy_app::prelude::*;
 use bevy_ecs::component::ComponentId;
 use bevy_ecs::prelude::*;
 use bevy_platform::collections::HashMap;
-use bevy_reflect::{TypeRegistration, prelude::*};
+use bevy_reflect::{
+    FromReflect, GetTypeRegistration, PartialReflect, ReflectFromReflect, ReflectKind, TypePath,
+    TypeRegistration, TypeRegistry, prelude::*, serde::TypedReflectDeserializer,
+};
 use builtin::CoreCVarsPlugin;
 use builtin::LogCVarChanges;
 #[cfg(feature = "parse_cvars")]
 use parse::CVarOverride;
 use reflect::CVarMeta;
-use serde::Deserializer;
 #[cfg(feature = "parse_cvars")]
 use serde::de::IntoDeserializer as _;
+use serde::{Deserialize as _, Deserializer, de::DeserializeSeed as _};
 
 pub mod defaults;
 mod error;
@@ -29,12 +32,23 @@ mod types;
 pub use error::*;
 pub use types::*;
 pub mod builtin;
+#[cfg(feature = "parse_cvars")]
+pub mod layers;
+#[cfg(feature = "parse_cvars")]
+pub mod format;
 #[cfg(feature = "config_loader")]
 pub mod loader;
 #[cfg(feature = "parse_cvars")]
 pub mod parse;
 #[cfg(feature = "parse_cvars")]
+pub mod presets;
+#[cfg(feature = "parse_cvars")]
 pub mod save;
+#[cfg(feature = "parse_cvars")]
+pub mod script;
+#[cfg(feature = "config_loader")]
+pub mod storage;
+pub mod path;
 pub mod prelude;
 pub mod reflect;
 #[cfg(feature = "parse_cvars")]
@@ -54,16 +68,54 @@ pub mod reexports {
     pub use bevy_app;
     pub use bevy_ecs;
     pub use bevy_reflect;
-    pub mod jank {
-        pub use crate::reflect::ReflectCVar as ReflectCVar__MACRO_JANK;
-        pub use bevy_ecs::reflect::ReflectResource as ReflectResource__MACRO_JANK;
-        pub use bevy_reflect::prelude::ReflectDefault as ReflectDefault__MACRO_JANK;
-    }
 }
 
+/// Derives [CVarMeta] and the other traits a CVar needs, for declaring individual CVars.
+///
+/// # Example
+/// ```rust
+/// # use bevy_app::prelude::*;
+/// # use bevy_reflect::Reflect;
+/// # use bevy_convars::prelude::*;
+/// # use bevy_convars::CVar;
+/// /// The strength of the bloom effect.
+/// #[derive(Reflect, CVar, Clone, PartialEq)]
+/// #[cvar(path = "render.bloom.intensity", flags = SAVED | RUNTIME, default = 0.3, range = 0.0..=1.0, alias = "render.bloom_intensity")]
+/// pub(crate) struct BloomIntensity(f32);
+///
+/// let mut app = App::new();
+/// app.add_plugins(CVarsPlugin);
+/// app.register_cvar::<BloomIntensity>();
+/// ```
+///
+/// # Attributes
+/// All options are provided through `#[cvar(...)]`:
+/// - `path = "a.b.c"`: The path of the CVar, required.
+/// - `flags = SAVED | RUNTIME`: The [CVarFlags] of the CVar. Bare identifiers refer to the associated constants of [CVarFlags]. Defaults to [CVarFlags::LOCAL].
+/// - `default = expr`: The default value of the CVar, also used to implement [Default]. If omitted, you must implement [Default] for the CVar yourself.
+/// - `description = "..."`: The description of the CVar. If omitted, the doc comment of the type is used.
+/// - `alias = "x.y.z"`: An alternative path for the CVar, can be repeated. Aliases can't overlap the path or each other.
+/// - `constraint = path::to::fn`: A function callable as `fn(&Inner) -> Result<(), String>` that values must pass, see [CVarMeta::validate].
+/// - `range = start..=end`: Restricts values to the given range.
+///
+/// The type must also derive [Reflect](bevy_reflect::Reflect), and have exactly one field holding the CVar's value.
+///
+/// ```compile_fail
+/// # use bevy_reflect::Reflect;
+/// # use bevy_convars::CVar;
+/// // Fails to compile, as `render.bloom` can't be both a value and a table.
+/// #[derive(Reflect, CVar, Clone, PartialEq)]
+/// #[cvar(path = "render.bloom", default = true, alias = "render.bloom.enabled")]
+/// pub(crate) struct Bloom(bool);
+/// ```
+pub use bevy_convars_macros::CVar;
+
+// Allows the derive macro's output to refer to this crate by name, even from within it.
+extern crate self as bevy_convars;
+
 /// Core plugin for providing CVars.
 /// # Remarks
-/// Needs to be registered before any of the generated plugins to ensure [CVarManagement] is available.
+/// This can be added before or after the generated plugins, [CVarManagement] is created by whichever needs it first.
 pub struct CVarsPlugin;
 
 #[derive(Debug)]
@@ -90,19 +142,26 @@ struct CVarTreeEditContext {
 }
 
 impl CVarTreeNode {
-    pub fn children(&self) -> Option<impl Iterator<Item = (&'_ &'static str, &'_ CVarTreeNode)>> {
+    #[cfg_attr(not(feature = "config_loader"), allow(dead_code))]
+    pub fn child(&self, key: &str) -> Option<&CVarTreeNode> {
         match self {
             CVarTreeNode::Leaf { name: _, reg: _ } => None,
-            CVarTreeNode::Branch { descendants } => Some(descendants.iter()),
+            CVarTreeNode::Branch { descendants } => descendants.get(key),
         }
     }
 
+    #[cfg_attr(not(feature = "config_loader"), allow(dead_code))]
     pub fn is_leaf(&self) -> bool {
         matches!(self, CVarTreeNode::Leaf { .. })
     }
 
     pub fn insert(&mut self, name: &'static str, id: ComponentId) {
-        let segments: Vec<&'static str> = name.split('.').collect();
+        self.insert_at(name, name, id);
+    }
+
+    /// Inserts a CVar into the tree at the given path, which may differ from its name if it's an alias.
+    pub fn insert_at(&mut self, path: &'static str, name: &'static str, id: ComponentId) {
+        let segments: Vec<&'static str> = path.split('.').collect();
         let edit_ctx = CVarTreeEditContext { new_cvar: name };
 
         let mut cur = self;
@@ -195,20 +254,34 @@ pub struct CVarManagement {
     pub(crate) resources: HashMap<ComponentId, TypeRegistration>,
     /// An index of all CVars and their types.
     pub(crate) tree: CVarTreeNode,
+    /// The stack of config layers CVar values are resolved from.
+    #[cfg(feature = "parse_cvars")]
+    pub(crate) layers: layers::LayerStack,
+    /// Config values that didn't match any registered CVar, waiting for it to be registered.
+    #[cfg(feature = "config_loader")]
+    pub(crate) pending: HashMap<String, loader::PendingCVar>,
+    /// Orphaned user config keys that were purged, and should be removed from the user config when it's next saved.
+    #[cfg(feature = "config_loader")]
+    pub(crate) purged_orphans: bevy_platform::collections::HashSet<String>,
 }
 
 impl CVarManagement {
     /// Register a CVar of the given type to the internal storage.
     #[doc(hidden)]
-    pub fn register_cvar<T: Reflect + Resource + CVarMeta>(&mut self, app: &mut App) {
+    pub fn register_cvar<T: Reflect + Resource + CVarMeta>(&mut self, world: &World) {
         let registration = {
-            let registry = app.world().resource::<AppTypeRegistry>();
+            let registry = world.resource::<AppTypeRegistry>();
             let registry = registry.read();
             registry.get(::std::any::TypeId::of::<T>()).unwrap().clone()
         };
-        let cid = app.world().resource_id::<T>().unwrap();
+        let cid = world.resource_id::<T>().unwrap();
 
         self.tree.insert(T::CVAR_PATH, cid);
+
+        for alias in T::ALIASES {
+            self.tree.insert_at(alias, T::CVAR_PATH, cid);
+        }
+
         self.resources.insert(cid, registration);
     }
 
@@ -339,19 +412,9 @@ impl CVarManagement {
         let reflect_cvar = ty_reg.data::<reflect::ReflectCVar>().unwrap();
 
         let value_patch = {
-            let field_0 = reflect_cvar.inner_type();
-
             let registry = world.resource::<AppTypeRegistry>().read();
 
-            let deserializer = registry
-                .get(field_0)
-                .ok_or(CVarError::BadCVarType)?
-                .data::<ReflectDeserialize>()
-                .ok_or(CVarError::CannotDeserialize)?;
-
-            deserializer
-                .deserialize(value)
-                .map_err(|e| CVarError::FailedDeserialize(format!("{e:?}")))?
+            Self::deserialize_inner(&registry, reflect_cvar, value)?
         };
 
         let reflect_res = ty_reg.data::<ReflectResource>().unwrap();
@@ -382,19 +445,9 @@ impl CVarManagement {
         let reflect_cvar = ty_reg.data::<reflect::ReflectCVar>().unwrap();
 
         let value_patch = {
-            let field_0 = reflect_cvar.inner_type();
-
             let registry = world.resource::<AppTypeRegistry>().read();
 
-            let deserializer = registry
-                .get(field_0)
-                .ok_or(CVarError::CannotDeserialize)?
-                .data::<ReflectDeserialize>()
-                .ok_or(CVarError::CannotDeserialize)?;
-
-            deserializer
-                .deserialize(value)
-                .map_err(|e| CVarError::FailedDeserialize(format!("{e:?}")))?
+            Self::deserialize_inner(&registry, reflect_cvar, value)?
         };
 
         let reflect_res = ty_reg.data::<ReflectResource>().unwrap();
@@ -409,6 +462,159 @@ impl CVarManagement {
         Ok(())
     }
 
+    /// Checks whether the given deserializable value would be accepted by a CVar (including its constraints), without applying it.
+    pub fn check_cvar_deserialize<'a>(
+        &self,
+        world: &World,
+        cvar: &str,
+        value: impl Deserializer<'a>,
+    ) -> Result<(), CVarError> {
+        let cid = self.tree.get(cvar).ok_or(CVarError::UnknownCVar)?;
+
+        let ty_reg = self.resources.get(&cid).ok_or(CVarError::MissingCid)?;
+
+        let reflect_cvar = ty_reg.data::<reflect::ReflectCVar>().unwrap();
+
+        let value_patch = self.deserialize_cvar(world, cvar, value)?;
+
+        let reflect_res = ty_reg.data::<ReflectResource>().unwrap();
+
+        let cvar = reflect_res.reflect(world)?;
+        let inner = reflect_cvar.reflect_inner(cvar.as_partial_reflect())?;
+
+        // Values that can't be cloned can't be checked ahead of time.
+        let Ok(mut candidate) = inner.reflect_clone() else {
+            return Ok(());
+        };
+
+        candidate.try_apply(value_patch.as_partial_reflect())?;
+
+        reflect_cvar
+            .validate(candidate.as_partial_reflect())
+            .map_err(CVarError::ConstraintViolation)
+    }
+
+    /// Deserializes a value for a CVar without applying it, see [CVarManagement::deserialize_inner].
+    /// The result may be a dynamic value, i.e. for struct CVars, so compare it through reflection.
+    pub(crate) fn deserialize_cvar<'a>(
+        &self,
+        world: &World,
+        cvar: &str,
+        value: impl Deserializer<'a>,
+    ) -> Result<Box<dyn PartialReflect>, CVarError> {
+        let cid = self.tree.get(cvar).ok_or(CVarError::UnknownCVar)?;
+
+        let ty_reg = self.resources.get(&cid).ok_or(CVarError::MissingCid)?;
+
+        let reflect_cvar = ty_reg.data::<reflect::ReflectCVar>().unwrap();
+
+        let registry = world.resource::<AppTypeRegistry>().read();
+
+        Self::deserialize_inner(&registry, reflect_cvar, value)
+    }
+
+    /// Deserializes a new value for a CVar.
+    /// # Remarks
+    /// Types with serde support, and types reflection can deserialize structurally (structs, lists, enums, etc), are deserialized through reflection.
+    /// Opaque types without serde support are instead deserialized from a string using [ReflectCVarString](reflect::ReflectCVarString), if available.
+    fn deserialize_inner<'a>(
+        registry: &TypeRegistry,
+        reflect_cvar: &reflect::ReflectCVar,
+        value: impl Deserializer<'a>,
+    ) -> Result<Box<dyn PartialReflect>, CVarError> {
+        let registration = registry
+            .get(reflect_cvar.inner_type())
+            .ok_or(CVarError::BadCVarType)?;
+
+        if registration.data::<ReflectDeserialize>().is_none()
+            && registration.type_info().kind() == ReflectKind::Opaque
+        {
+            let from_str = registration
+                .data::<reflect::ReflectCVarString>()
+                .ok_or(CVarError::CannotDeserialize)?;
+
+            let text = String::deserialize(value)
+                .map_err(|e| CVarError::FailedDeserialize(format!("{e:?}")))?;
+
+            return from_str
+                .from_str(&text)
+                .map_err(CVarError::FailedDeserialize);
+        }
+
+        let value = TypedReflectDeserializer::new(registration, registry)
+            .deserialize(value)
+            .map_err(|e| CVarError::FailedDeserialize(format!("{e:?}")))?;
+
+        // Deserialized lists, maps and structs are dynamic, convert them so they replace the CVar's value rather than being merged into it.
+        let concrete = registration
+            .data::<ReflectFromReflect>()
+            .and_then(|x| x.from_reflect(value.as_ref()));
+
+        Ok(match concrete {
+            Some(concrete) => concrete.into_partial_reflect(),
+            None => value,
+        })
+    }
+
+    /// Set a CVar by parsing the given string.
+    /// # Remarks
+    /// If the CVar's type implements [FromStr](std::str::FromStr) and [Display](std::fmt::Display), that implementation is used.
+    /// Otherwise the string is parsed as a TOML value, falling back to treating it as a plain string (so `Fxaa` works as well as `"Fxaa"`).
+    ///
+    /// Use the WorldExtensions version if you can, it handles the invariants. This is harder to call than it looks due to needing mutable world.
+    pub fn set_cvar_from_str(
+        &self,
+        world: &mut World,
+        cvar: &str,
+        value: &str,
+    ) -> Result<(), CVarError> {
+        let cid = self.tree.get(cvar).ok_or(CVarError::UnknownCVar)?;
+
+        let ty_reg = self.resources.get(&cid).ok_or(CVarError::MissingCid)?;
+
+        let reflect_cvar = ty_reg.data::<reflect::ReflectCVar>().unwrap();
+
+        let from_str = {
+            let registry = world.resource::<AppTypeRegistry>().read();
+
+            registry
+                .get_type_data::<reflect::ReflectCVarString>(reflect_cvar.inner_type())
+                .cloned()
+        };
+
+        if let Some(from_str) = from_str {
+            let value = from_str
+                .from_str(value)
+                .map_err(CVarError::FailedDeserialize)?;
+
+            let reflect_res = ty_reg.data::<ReflectResource>().unwrap();
+
+            let cvar = reflect_res.reflect_mut(world)?;
+
+            return reflect_cvar
+                .reflect_apply(cvar.into_inner().as_partial_reflect_mut(), value.as_ref());
+        }
+
+        #[cfg(feature = "parse_cvars")]
+        {
+            use std::str::FromStr as _;
+
+            let value = toml_edit::Value::from_str(value)
+                .unwrap_or_else(|_| toml_edit::Value::from(value.trim()));
+
+            self.set_cvar_deserialize(world, cvar, value.into_deserializer())
+        }
+
+        #[cfg(not(feature = "parse_cvars"))]
+        Err(CVarError::CannotDeserialize)
+    }
+
+    /// Returns the stack of config layers CVar values are resolved from, see [layers].
+    #[cfg(feature = "parse_cvars")]
+    pub fn layers(&self) -> &layers::LayerStack {
+        &self.layers
+    }
+
     /// Returns an iterator for all CVar type registrations.
     pub fn iterate_cvar_types(&self) -> impl Iterator<Item = &TypeRegistration> {
         self.resources.values()
@@ -446,6 +652,15 @@ pub trait WorldExtensions {
         })
     }
 
+    /// Set a CVar on the world by parsing the given string, see [CVarManagement::set_cvar_from_str].
+    fn set_cvar_from_str(&mut self, cvar: &str, value: &str) -> Result<(), CVarError> {
+        let cell = self.as_world();
+
+        cell.resource_scope::<CVarManagement, _>(|w, management| {
+            management.set_cvar_from_str(w, cvar, value)
+        })
+    }
+
     /// Set a CVar on the world through reflection
     fn set_cvar_reflect(&mut self, cvar: &str, value: &dyn Reflect) -> Result<(), CVarError> {
         let cell = self.as_world();
@@ -471,6 +686,10 @@ pub trait WorldExtensions {
     /// Set a CVar on the world using the provided override.
     /// # Remarks
     /// CVar overrides, by design, bypass change detection to look like the default value of the CVar.
+    ///
+    /// The value is set outside of the layer stack, so any layer containing the CVar that's applied afterwards overwrites it,
+    /// i.e. the user's config when the [CVarLoaderPlugin](crate::loader::CVarLoaderPlugin) finishes. Overrides applied at startup
+    /// should instead be added as a [LayerKind::CommandLine](layers::LayerKind::CommandLine) layer, see [ConfigLayer::from_overrides](layers::ConfigLayer::from_overrides).
     #[cfg(feature = "parse_cvars")]
     fn set_cvar_with_override(&mut self, r#override: &CVarOverride) -> Result<(), CVarError> {
         let cell = self.as_world();
@@ -483,6 +702,79 @@ pub trait WorldExtensions {
             )
         })
     }
+
+    /// Adds a config layer to the stack, recomputing the CVars it contains. See [layers] for how values are resolved.
+    /// # Remarks
+    /// CVars that fail to apply are logged and otherwise ignored.
+    #[cfg(feature = "parse_cvars")]
+    fn add_cvar_layer(&mut self, layer: layers::ConfigLayer) -> layers::LayerId {
+        layers::add_layer(self.as_world(), layer).0
+    }
+
+    /// Removes a config layer from the stack, recomputing the CVars it contained.
+    /// CVars no longer contained by any layer are reset to their default value, as if they were never changed.
+    #[cfg(feature = "parse_cvars")]
+    fn remove_cvar_layer(&mut self, id: layers::LayerId) -> Option<layers::ConfigLayer> {
+        layers::remove_layer(self.as_world(), id)
+    }
+
+    /// Replaces the contents of a config layer while keeping its position, recomputing the CVars either version contains.
+    /// Returns false if there's no such layer.
+    #[cfg(feature = "parse_cvars")]
+    fn replace_cvar_layer(&mut self, id: layers::LayerId, layer: layers::ConfigLayer) -> bool {
+        layers::replace_layer(self.as_world(), id, layer).is_some()
+    }
+
+    /// Applies every value of the named preset as a batch, see [presets].
+    /// Every value is checked before any is applied, so a preset with a bad value doesn't end up partially applied.
+    /// # Remarks
+    /// Values are applied like [WorldExtensions::set_cvar_deserialize], so they're saved like any other change.
+    #[cfg(feature = "parse_cvars")]
+    fn apply_cvar_preset(&mut self, name: &str) -> Result<(), CVarError> {
+        presets::apply_preset(self.as_world(), name)
+    }
+
+    /// Moves a config layer to the given position in the stack (0 being the lowest priority), recomputing the CVars it contains.
+    /// Returns false if there's no such layer.
+    #[cfg(feature = "parse_cvars")]
+    fn move_cvar_layer(&mut self, id: layers::LayerId, index: usize) -> bool {
+        layers::move_layer(self.as_world(), id, index)
+    }
+
+    /// Runs one or more lines of a `.cfg` script from a console, i.e. `set render.aa.method Fxaa`, see [script].
+    /// Values are set on the console's [LayerKind::Runtime](layers::LayerKind::Runtime) layer, and `exec`ed scripts are relative to the
+    /// [ScriptDirectory](script::ScriptDirectory), if any.
+    ///
+    /// Returns the first error encountered, though the remaining commands are still run.
+    #[cfg(feature = "parse_cvars")]
+    fn exec_cvar_command(&mut self, command: &str) -> Result<(), CVarError> {
+        let script = script::CVarScript::parse(command, script::CONSOLE_SOURCE)?;
+
+        script::run_console_script(self.as_world(), &script)
+    }
+
+    /// Runs a `.cfg` script as if each of its lines were given to [WorldExtensions::exec_cvar_command].
+    #[cfg(feature = "parse_cvars")]
+    fn exec_cvar_script(&mut self, script: &script::CVarScript) -> Result<(), CVarError> {
+        script::run_console_script(self.as_world(), script)
+    }
+
+    /// Saves the world's CVars to the user's config, through the [UserConfigStorage](storage::UserConfigStorage) set up by the
+    /// [CVarLoaderPlugin](loader::CVarLoaderPlugin). See [CVarStorage::save_world](storage::CVarStorage::save_world).
+    ///
+    /// Returns false if no storage for the user's config was set.
+    #[cfg(feature = "config_loader")]
+    fn save_user_config(&mut self) -> Result<bool, CVarError> {
+        let world = self.as_world();
+
+        let Some(storage) = world.get_resource::<storage::UserConfigStorage>() else {
+            return Ok(false);
+        };
+
+        storage.0.save_world(world)?;
+
+        Ok(true)
+    }
 }
 
 impl WorldExtensions for World {
@@ -491,11 +783,90 @@ impl WorldExtensions for World {
     }
 }
 
+/// Provides extensions to the app for CVars.
+pub trait AppExtensions {
+    /// Registers a CVar, inserting it into the world with its default value.
+    /// # Remarks
+    /// This is done for you by the plugins generated by [cvar_collection!], you only need to call it for CVars declared with [derive@CVar].
+    fn register_cvar<T>(&mut self) -> &mut Self
+    where
+        T: CVarMeta + Default + FromReflect + TypePath + GetTypeRegistration,
+        T::Inner: GetTypeRegistration;
+
+    /// Registers a named preset, replacing any existing preset with the same name. See [presets].
+    #[cfg(feature = "parse_cvars")]
+    fn register_cvar_preset(&mut self, preset: presets::CVarPreset) -> &mut Self;
+}
+
+impl AppExtensions for App {
+    fn register_cvar<T>(&mut self) -> &mut Self
+    where
+        T: CVarMeta + Default + FromReflect + TypePath + GetTypeRegistration,
+        T::Inner: GetTypeRegistration,
+    {
+        self.register_type::<T>();
+        self.register_type::<T::Inner>();
+
+        {
+            let mut registry = self.world().resource::<AppTypeRegistry>().write();
+
+            registry.register_type_data::<T, ReflectDefault>();
+            registry.register_type_data::<T, ReflectResource>();
+            registry.register_type_data::<T, reflect::ReflectCVar>();
+
+            T::register_value_type_data(&mut registry);
+        }
+
+        self.insert_resource(T::default());
+
+        self.world_mut().init_resource::<CVarManagement>();
+
+        self.world_mut()
+            .resource_scope::<CVarManagement, _>(|world, mut management| {
+                management.register_cvar::<T>(world);
+            });
+
+        // Yes, these always run. I doubt it matters, but they do.
+        self.add_systems(Last, cvar_modified_system::<T>);
+
+        // Ensure our default values stay marked as default by incrementing the change tick.
+        // Due to how change ticks work there's zero consequences.
+        self.world_mut().increment_change_tick();
+
+        // Apply any config values that were loaded before this CVar existed.
+        #[cfg(feature = "config_loader")]
+        for path in std::iter::once(T::CVAR_PATH).chain(T::ALIASES.iter().copied()) {
+            loader::apply_pending_cvar(self.world_mut(), T::CVAR_PATH, path);
+        }
+
+        #[cfg(feature = "parse_cvars")]
+        {
+            self.world_mut()
+                .resource_mut::<CVarManagement>()
+                .layers
+                .canonicalize(T::CVAR_PATH, T::ALIASES);
+
+            layers::recompute_cvars(self.world_mut(), [T::CVAR_PATH.to_owned()]);
+        }
+
+        self
+    }
+
+    #[cfg(feature = "parse_cvars")]
+    fn register_cvar_preset(&mut self, preset: presets::CVarPreset) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<presets::CVarPresets>()
+            .register(preset);
+
+        self
+    }
+}
+
 impl Plugin for CVarsPlugin {
     fn build(&self, app: &mut bevy_app::App) {
         app.register_type::<CVarFlags>();
 
-        app.insert_resource::<CVarManagement>(CVarManagement::default());
+        app.init_resource::<CVarManagement>();
         app.add_plugins(CoreCVarsPlugin);
     }
 }
@@ -1,74 +1,354 @@
 //! Provides the ability to load TOML configuration files as a collection of CVars.
 //!
+//! # Includes
+//! A config can include other configs with a root `include` key (see [INCLUDE_KEY]), i.e. `include = ["graphics/high.toml", "?local.toml"]`.
+//! Paths are resolved relative to the including file for files loaded with [ConfigLoader::load_file] (including the user's config),
+//! and relative to the including asset for [CVarConfig] assets. Entries prefixed with `?` are skipped if they don't exist.
+//!
+//! Each included config becomes its own [DocumentContext] (and layer), named after its own path, applied before the config including it.
+//!
+//! # Conditional sections
+//! Values that only apply under some condition go in a `[when."condition"]` section (see [WHEN_KEY] for the supported conditions),
+//! i.e. `[when.debug_assertions]` or `[when."render.backend == 'gl'"]`. Sections are evaluated when the config is applied,
+//! and those that hold are merged on top of the rest of the config in order. Skipped sections are logged along with the reason.
+//!
+//! # Unknown keys
+//! Keys that don't match any registered CVar (i.e. typos) are retained silently by default. A loader can instead report them,
+//! along with their position and the closest CVar path, or reject the layer entirely, see [UnknownKeyPolicy].
+//!
+//! # Reports
+//! Applying a config returns a [ConfigLoadReport], listing every key that was applied, skipped (along with why) or rejected by its CVar,
+//! each with the source and line/column it was written at.
+//!
 //! # Recommendations
 //! No default for the user's config file is provided, however one can use the [directories](https://crates.io/crates/directories) library to get platform-specific locations for those files.
+//! Platforms without a filesystem can store the user's config elsewhere with a [CVarStorage](crate::storage::CVarStorage).
 //!
 
-use std::{fmt::Display, fs::File, io::Read, path::PathBuf};
+#[cfg(feature = "config_loader_fs")]
+use std::path::{Component, Path, PathBuf};
+use std::{
+    fmt::Display,
+    marker::PhantomData,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
-use bevy_app::Plugin;
+use bevy_app::{Last, Plugin};
 use bevy_ecs::world::World;
 use bevy_log::warn;
-use serde::de::IntoDeserializer;
-use toml_edit::{ImDocument, TomlError};
+use bevy_platform::collections::HashMap;
+use toml_edit::TomlError;
 
 #[cfg(feature = "config_loader_asset")]
 mod assets;
+mod conditions;
 mod cvar_doc;
+#[cfg(feature = "config_loader_asset")]
+mod hot_reload;
+mod include;
+mod pending;
+mod report;
+#[cfg(feature = "config_loader_asset")]
+mod startup;
+mod strict;
 #[cfg(test)]
 mod tests;
 
 #[cfg(feature = "config_loader_asset")]
 pub use assets::*;
 
+pub use conditions::{ConfigFeatures, WHEN_KEY};
 pub use cvar_doc::*;
-
-use crate::{CVarError, CVarManagement, WorldExtensions};
+#[cfg(feature = "config_loader_asset")]
+pub use hot_reload::CVarConfigHotReloadPlugin;
+pub use include::INCLUDE_KEY;
+pub use pending::PendingCVar;
+pub(crate) use pending::apply_pending_cvar;
+pub use report::{ConfigLoadReport, ReportEntry, SkipReason};
+#[cfg(feature = "config_loader_asset")]
+pub use startup::{CVarLoadSet, CVarLoadState, ConfigLayerLoadFailed};
+pub use strict::{UnknownKey, UnknownKeyPolicy};
+
+use crate::{
+    CVarError, CVarManagement,
+    format::{ConfigFormat, FormatError, TomlFormat},
+    layers::{ConfigLayer, LayerId, LayerKind},
+    storage::{
+        CVarStorage, UserConfigSaveOptions, UserConfigStorage, auto_save_user_config,
+        start_auto_save,
+    },
+};
+#[cfg(feature = "config_loader_fs")]
+use crate::{
+    script::{AUTOEXEC, CVarScript, ScriptDirectory},
+    storage::FileStorage,
+};
+
+/// The key of the table in the user config recording when each orphaned key was first found, as seconds since the unix epoch.
+/// See [CVarManagement::orphans].
+pub const ORPHAN_METADATA_KEY: &str = "_orphaned_since";
+
+/// A layer scanned from a config, along with the keys that didn't match any registered CVar and a report of the keys found.
+pub(crate) type ScannedLayer = (ConfigLayer, Vec<(String, PendingCVar)>, ConfigLoadReport);
 
 /// A config loader, which injests [DocumentContext]s and applies them to the world.
-#[derive(Default)]
-pub struct ConfigLoader {}
+///
+/// Configs read as text (i.e. with [ConfigLoader::apply_from_string] or [ConfigLoader::load_file]) are parsed in the loader's format,
+/// TOML unless set otherwise with [ConfigLoader::with_format].
+#[derive(Clone, Copy, Debug)]
+pub struct ConfigLoader<F = TomlFormat> {
+    /// How keys that don't match any registered CVar are treated.
+    unknown_keys: UnknownKeyPolicy,
+    /// The format configs are read in.
+    format: PhantomData<F>,
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        Self {
+            unknown_keys: UnknownKeyPolicy::default(),
+            format: PhantomData,
+        }
+    }
+}
 
 /// Methods for creating a config loader.
-impl ConfigLoader {
-    /// Applies a given config to the world.
+impl<F: ConfigFormat> ConfigLoader<F> {
+    /// Sets how keys that don't match any registered CVar are treated by the layers this loader applies.
+    pub fn with_unknown_keys(self, policy: UnknownKeyPolicy) -> Self {
+        Self {
+            unknown_keys: policy,
+            ..self
+        }
+    }
+
+    /// Sets the format configs are read in.
+    /// # Example
+    /// ```
+    /// # use bevy_convars::loader::ConfigLoader;
+    /// # #[cfg(feature = "format_json")]
+    /// let loader = ConfigLoader::default().with_format::<bevy_convars::format::JsonFormat>();
+    /// ```
+    pub fn with_format<G: ConfigFormat>(self) -> ConfigLoader<G> {
+        ConfigLoader {
+            unknown_keys: self.unknown_keys,
+            format: PhantomData,
+        }
+    }
+
+    /// Returns how keys that don't match any registered CVar are treated.
+    pub fn unknown_keys(&self) -> UnknownKeyPolicy {
+        self.unknown_keys
+    }
+
+    /// Applies a given config to the world, as a new [LayerKind::User] layer if it's the user's config or a [LayerKind::Embedded] layer otherwise.
+    ///
+    /// Keys that don't match any registered CVar are retained, and applied if a CVar with that path is registered later.
+    /// Those from the user's config are additionally kept as orphans, so saving doesn't lose them (see [CVarManagement::orphans]).
+    ///
+    /// Returns a report of every key that was applied, skipped or failed to apply, see [ConfigLoader::apply_layer].
     pub fn apply<S: AsRef<str>>(
         &self,
         world: &mut World,
         document: DocumentContext<S>,
         user_config: bool,
-    ) -> Result<(), CVarError> {
-        let scanner = CVarDocScanner::new(document, user_config);
+    ) -> Result<ConfigLoadReport, CVarError> {
+        let kind = if user_config {
+            LayerKind::User
+        } else {
+            LayerKind::Embedded
+        };
+
+        let (_, report) = self.apply_layer(world, document, kind)?;
+
+        Ok(report)
+    }
+
+    /// Applies a given config to the world as a new layer of the given kind, returning its ID. See [layers](crate::layers).
+    ///
+    /// CVars that fail to apply don't stop the remaining ones from being applied, and are listed in the returned [ConfigLoadReport]
+    /// along with every key that was applied or skipped.
+    /// Unknown keys are reported according to [ConfigLoader::unknown_keys], and may reject the layer before anything is applied.
+    pub fn apply_layer<S: AsRef<str>>(
+        &self,
+        world: &mut World,
+        document: DocumentContext<S>,
+        kind: LayerKind,
+    ) -> Result<(LayerId, ConfigLoadReport), CVarError> {
+        let (layer, pending, mut report) = self.scan_document(world, document, kind)?;
+        let source = layer.source().to_owned();
+
+        let (id, failures) = crate::layers::add_layer(world, layer);
+
+        pending::store_pending(world, id, pending);
 
-        let cvars: Vec<(&str, toml_edit::Item)> =
-            scanner.find_cvars(world.resource::<CVarManagement>());
+        report.record_failures(world.resource::<CVarManagement>(), &source, failures);
 
-        for (cvar, value) in cvars {
-            if let toml_edit::Item::Value(value) = value {
-                world.set_cvar_deserialize(cvar, IntoDeserializer::into_deserializer(value))?;
-            } else {
-                warn!("CVar {cvar} couldn't be parsed, as it wasn't value-compatible.");
+        Ok((id, report))
+    }
+
+    /// Scans a config into a layer of the given kind, also returning the keys that don't match any registered CVar
+    /// and a report of the keys found. Unknown keys are reported according to [ConfigLoader::unknown_keys].
+    pub(crate) fn scan_document<S: AsRef<str>>(
+        &self,
+        world: &World,
+        document: DocumentContext<S>,
+        kind: LayerKind,
+    ) -> Result<ScannedLayer, CVarError> {
+        // Included configs are never written to, so only the user's config itself keeps track of orphans.
+        let user_config = kind == LayerKind::User && document.included_from().is_none();
+        let source = document.source().to_owned();
+        let scanner = CVarDocScanner::new(document, user_config);
+
+        let ScannedDocument {
+            cvars,
+            unmatched,
+            unretained,
+            mut report,
+        } = scanner.scan(world);
+
+        let unknown = strict::check_unknown_keys(
+            world.resource::<CVarManagement>(),
+            self.unknown_keys,
+            &source,
+            unmatched.iter().chain(unretained.iter()),
+        )?;
+
+        report.skip_unknown(unknown);
+
+        let now = SystemTime::now();
+
+        let pending = unmatched
+            .into_iter()
+            .filter_map(|(path, value, location)| {
+                let value = value.into_value().ok()?;
+                let orphaned_since =
+                    user_config.then(|| scanner.orphaned_since(&path).unwrap_or(now));
+
+                Some((
+                    path,
+                    PendingCVar {
+                        value,
+                        source: source.clone(),
+                        user_config,
+                        orphaned_since,
+                        location,
+                        layer: None,
+                    },
+                ))
+            })
+            .collect();
+
+        let mut layer = ConfigLayer::new(kind, source);
+
+        for (cvar, value, location) in cvars {
+            let entry = ReportEntry::new(cvar.to_owned(), layer.source(), location);
+
+            // Standard tables and arrays of tables are converted to their inline equivalents, so struct, map and list CVars
+            // can be written in whichever form reads best.
+            match (value.into_value(), location) {
+                (Ok(value), Some(location)) => layer.insert_with_location(cvar, value, location),
+                (Ok(value), None) => layer.insert(cvar, value),
+                (Err(_), _) => {
+                    warn!("CVar {cvar} couldn't be parsed, as it wasn't value-compatible.");
+                    report.skip(entry, SkipReason::NotValueCompatible);
+                    continue;
+                }
             }
+
+            report.applied.push(entry);
         }
 
-        Ok(())
+        Ok((layer, pending, report))
     }
 
-    /// Applies a given config to the world, by parsing it into a TOML document and [ConfigLoader::apply]ing that.
+    /// Applies a given config to the world, by parsing it in the loader's format and [ConfigLoader::apply]ing that.
     pub fn apply_from_string(
         &self,
         world: &mut World,
         document: &str,
         source: Option<&str>,
         user_config: bool,
-    ) -> Result<(), CVarError> {
-        let document = ImDocument::parse(document)?;
+    ) -> Result<ConfigLoadReport, CVarError> {
+        let document = DocumentContext::parse::<F>(
+            document.to_owned(),
+            source.unwrap_or("NO_SOURCE").to_owned(),
+        )?;
+
+        self.apply(world, document, user_config)
+    }
+
+    /// Reads a config file from the OS filesystem along with every config it includes, returning them in the order they should be applied.
+    /// Includes are resolved relative to the file including them.
+    #[cfg(feature = "config_loader_fs")]
+    pub fn load_file(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<Vec<DocumentContext<String>>, ConfigLoaderError> {
+        fn read<F: ConfigFormat>(
+            path: &Path,
+        ) -> Result<DocumentContext<String>, ConfigLoaderError> {
+            let document = DocumentContext::parse::<F>(
+                std::fs::read_to_string(path)?,
+                path.to_string_lossy().into_owned(),
+            )?;
+
+            Ok(document)
+        }
+
+        /// Joins an include onto the directory of the including file, resolving `.` and `..` where possible.
+        fn resolve(parent: &str, include: &str) -> PathBuf {
+            let joined = Path::new(parent)
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join(include);
+            let mut path = PathBuf::new();
+
+            for component in joined.components() {
+                match component {
+                    Component::CurDir => {}
+                    Component::ParentDir
+                        if matches!(path.components().next_back(), Some(Component::Normal(_))) =>
+                    {
+                        path.pop();
+                    }
+                    component => path.push(component),
+                }
+            }
+
+            path
+        }
+
+        // Paths are canonicalized where possible so cycles are detected regardless of how a file is named.
+        let identity =
+            |path: &Path| std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+
+        let path = path.as_ref();
+        let mut stack = include::IncludeStack::new(identity(path), read::<F>(path)?);
 
-        let document = DocumentContext::new(document, source.unwrap_or("NO_SOURCE").to_owned());
+        while let Some((_, source, include)) = stack.next() {
+            let path = resolve(&source, &include.path);
+
+            let document = match read::<F>(&path) {
+                Ok(document) => document.with_included_from(source),
+                Err(ConfigLoaderError::IoError(e))
+                    if include.optional && e.kind() == std::io::ErrorKind::NotFound =>
+                {
+                    continue;
+                }
+                Err(e) => {
+                    return Err(ConfigLoaderError::Include {
+                        path: path.to_string_lossy().into_owned(),
+                        error: Box::new(e),
+                    });
+                }
+            };
 
-        self.apply(world, document, user_config)?;
+            stack.push(identity(&path), document)?;
+        }
 
-        Ok(())
+        Ok(stack.finish())
     }
 }
 
@@ -78,25 +358,52 @@ impl ConfigLoader {
 pub enum ConfigLoaderError {
     /// Wrapper over an inner parsing error.
     ParseError(TomlError),
+    /// Wrapper over an inner error from a format other than TOML, see [format](crate::format).
+    FormatError(FormatError),
     /// Wrapper over an inner IO error.
     IoError(std::io::Error),
+    /// A config included by another couldn't be loaded.
+    Include {
+        /// The path of the included config.
+        path: String,
+        /// The error that occurred while loading it.
+        error: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// A config ended up including itself, through the given chain of configs.
+    IncludeCycle(String),
 }
 
 impl Display for ConfigLoaderError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ConfigLoaderError::ParseError(toml_error) => write!(f, "{toml_error}"),
+            ConfigLoaderError::FormatError(error) => write!(f, "{error}"),
             ConfigLoaderError::IoError(error) => write!(f, "{error}"),
+            ConfigLoaderError::Include { path, error } => {
+                write!(f, "Failed to load the included config {path}: {error}")
+            }
+            ConfigLoaderError::IncludeCycle(cycle) => write!(f, "Include cycle: {cycle}"),
         }
     }
 }
 
+impl std::error::Error for ConfigLoaderError {}
+
 impl From<TomlError> for ConfigLoaderError {
     fn from(value: TomlError) -> Self {
         Self::ParseError(value)
     }
 }
 
+impl From<FormatError> for ConfigLoaderError {
+    fn from(value: FormatError) -> Self {
+        match value.downcast::<TomlError>() {
+            Ok(error) => Self::ParseError(*error),
+            Err(error) => Self::FormatError(error),
+        }
+    }
+}
+
 impl From<std::io::Error> for ConfigLoaderError {
     fn from(value: std::io::Error) -> Self {
         Self::IoError(value)
@@ -106,31 +413,141 @@ impl From<std::io::Error> for ConfigLoaderError {
 /// A builder to create a new [CVarLoaderPlugin]
 #[derive(Default)]
 pub struct CVarLoaderPluginBuilder {
-    /// The user's config file within the OS filesystem
+    /// Where the user's config is loaded from and saved to.
+    user_config: Option<Arc<dyn CVarStorage>>,
+    /// How long to wait after the last change to a saved CVar before saving the user's config, if it's saved automatically.
+    auto_save: Option<Duration>,
+    /// How the user's config is saved.
+    save_options: UserConfigSaveOptions,
+    /// The directory scripts are run from, including [AUTOEXEC].
     #[cfg(feature = "config_loader_fs")]
-    user_config_file: Option<PathBuf>,
+    script_directory: Option<PathBuf>,
     /// Any extra layers to load at startup.
     extra_layers: Vec<DocumentContext<String>>,
+    /// The asset directory to load the layers named by [ConfigLayers](crate::builtin::ConfigLayers) from.
+    #[cfg(feature = "config_loader_asset")]
+    asset_layer_root: Option<String>,
+    /// The features conditional sections can test for.
+    features: Vec<String>,
+    /// How keys that don't match any registered CVar are treated, for each kind of layer.
+    unknown_keys: HashMap<LayerKind, UnknownKeyPolicy>,
 }
 
 impl CVarLoaderPluginBuilder {
     /// The fancy default, loading layers from the asset path `ConfigLayers/` and automatically loading the default layers.
     /// Does not set the user config file path or add any extra layers.
+    /// # Remarks
+    /// Asset layers are skipped with a warning if the `AssetPlugin` or `StatesPlugin` is missing, see [CVarLoaderPluginBuilder::with_asset_layers].
     pub fn fancy() -> Self {
         Self {
+            #[cfg(feature = "config_loader_asset")]
+            asset_layer_root: Some("ConfigLayers/".to_owned()),
             ..Default::default()
         }
     }
 
-    /// Sets the user config file location.
+    /// Enables loading the layers named by [ConfigLayers](crate::builtin::ConfigLayers) as assets, from the given asset directory.
+    /// # Remarks
+    /// This requires the `AssetPlugin` and `StatesPlugin` (both part of `DefaultPlugins`). See [CVarLoadState] for waiting on the layers.
+    /// Without them, asset layers are skipped with a warning and the user's config is applied right away.
+    #[cfg(feature = "config_loader_asset")]
+    pub fn with_asset_layers(self, root: impl Into<String>) -> Self {
+        Self {
+            asset_layer_root: Some(root.into()),
+            ..self
+        }
+    }
+
+    /// Sets the user config file location, shorthand for [CVarLoaderPluginBuilder::with_user_config_storage] with a [FileStorage].
     #[cfg(feature = "config_loader_fs")]
     pub fn with_user_config_file(self, path: PathBuf) -> Self {
+        self.with_user_config_storage(FileStorage::new(path))
+    }
+
+    /// Sets where the user's config is loaded from and saved to, see [storage](crate::storage).
+    /// # Example
+    /// ```
+    /// # use bevy_convars::{loader::CVarLoaderPluginBuilder, storage::MemoryStorage};
+    /// // i.e. for tests, or platforms without a filesystem.
+    /// let builder = CVarLoaderPluginBuilder::default().with_user_config_storage(MemoryStorage::new());
+    /// ```
+    pub fn with_user_config_storage(self, storage: impl CVarStorage) -> Self {
+        Self {
+            user_config: Some(Arc::new(storage)),
+            ..self
+        }
+    }
+
+    /// Saves the user's config automatically once no [SAVED](crate::CVarFlags::SAVED) CVar has changed for the given duration,
+    /// and when the app exits (on [AppExit](bevy_app::AppExit)). Values are merged into the existing config, see [CVarStorage::save_world].
+    /// # Remarks
+    /// This does nothing unless the user's config is set, see [CVarLoaderPluginBuilder::with_user_config_storage].
+    /// With asset layers, changes are only watched for once [CVarLoadState::Ready] is reached, so loading isn't saved.
+    /// # Example
+    /// ```
+    /// # use std::time::Duration;
+    /// # use bevy_convars::{loader::CVarLoaderPluginBuilder, storage::MemoryStorage};
+    /// let builder = CVarLoaderPluginBuilder::default()
+    ///     .with_user_config_storage(MemoryStorage::new())
+    ///     .with_auto_save(Duration::from_secs(2));
+    /// ```
+    pub fn with_auto_save(self, debounce: Duration) -> Self {
         Self {
-            user_config_file: Some(path),
+            auto_save: Some(debounce),
             ..self
         }
     }
 
+    /// Sets how the user's config is saved, both by [WorldExtensions::save_user_config](crate::WorldExtensions::save_user_config) and automatically.
+    /// # Example
+    /// ```
+    /// # use bevy_convars::{loader::CVarLoaderPluginBuilder, storage::{MemoryStorage, UserConfigSaveOptions}};
+    /// // Settings reset to their default are removed from the user's config.
+    /// let builder = CVarLoaderPluginBuilder::default()
+    ///     .with_user_config_storage(MemoryStorage::new())
+    ///     .with_save_options(UserConfigSaveOptions {
+    ///         remove_defaults: true,
+    ///         ..Default::default()
+    ///     });
+    /// ```
+    pub fn with_save_options(self, options: UserConfigSaveOptions) -> Self {
+        Self {
+            save_options: options,
+            ..self
+        }
+    }
+
+    /// Sets the directory `.cfg` scripts are run from, see [script](crate::script).
+    /// [AUTOEXEC] is run from it at startup if it exists, and console `exec` commands are relative to it.
+    #[cfg(feature = "config_loader_fs")]
+    pub fn with_script_directory(self, path: PathBuf) -> Self {
+        Self {
+            script_directory: Some(path),
+            ..self
+        }
+    }
+
+    /// Enables a feature conditional sections can test for with `feature == '...'`, see [WHEN_KEY].
+    /// # Example
+    /// ```
+    /// # use bevy_convars::loader::CVarLoaderPluginBuilder;
+    /// // i.e. only when built with the app's `steam` feature.
+    /// let builder = CVarLoaderPluginBuilder::default().with_feature("steam");
+    /// ```
+    pub fn with_feature(mut self, feature: impl Into<String>) -> Self {
+        self.features.push(feature.into());
+
+        self
+    }
+
+    /// Sets how keys that don't match any registered CVar are treated for the given kind of layer, see [UnknownKeyPolicy].
+    /// Extra layers are [LayerKind::Embedded], asset layers [LayerKind::Asset] and the user's config [LayerKind::User].
+    pub fn with_unknown_keys(mut self, kind: LayerKind, policy: UnknownKeyPolicy) -> Self {
+        self.unknown_keys.insert(kind, policy);
+
+        self
+    }
+
     /// Adds a pre-parsed config layer to apply.
     pub fn add_layer(mut self, layer: DocumentContext<String>) -> Self {
         self.extra_layers.push(layer);
@@ -141,75 +558,199 @@ impl CVarLoaderPluginBuilder {
     /// Consumes the builder to create a [CVarLoaderPlugin].
     pub fn build(self) -> CVarLoaderPlugin {
         CVarLoaderPlugin {
-            user_config_file: self.user_config_file,
+            user_config: self.user_config,
+            auto_save: self.auto_save,
+            save_options: self.save_options,
+            #[cfg(feature = "config_loader_fs")]
+            script_directory: self.script_directory,
             extra_layers: self.extra_layers,
+            #[cfg(feature = "config_loader_asset")]
+            asset_layer_root: self.asset_layer_root,
+            features: self.features,
+            unknown_keys: self.unknown_keys,
         }
     }
 }
 
-/// Plugin that provides layered config loading for CVars, and additionally manages the user config file.
+/// Plugin that provides layered config loading for CVars, and additionally manages the user config (see [storage](crate::storage)).
+///
+/// Once all plugins are built (during [Plugin::finish]), the plugin will load any layers it was configured to load, and also any asset layers named by [ConfigLayers](crate::builtin::ConfigLayers)
 ///
-/// During build, the plugin will load any layers it was configured to load, and also any asset layers named by [ConfigLayers](crate::builtin::ConfigLayers)
+/// Layers are applied in the order: extra layers, asset layers, then the user's config. [AUTOEXEC] is run from the script directory (if set)
+/// as a [LayerKind::Script] layer, above all of them. When asset layers are enabled they load in the background,
+/// so the app starts in `CVarLoadState::Loading` and moves to `CVarLoadState::Ready` once every layer has been applied.
 ///
 /// # Remarks
-/// This plugin **MUST** be added after all other CVar registering plugins. It's recommended to seperate CVar registration from other plugin registration to ensure it's done first.
+/// This plugin can be added in any order relative to CVar registering plugins, as layers aren't applied until every plugin has been built.
+/// CVars registered later on (i.e. by a plugin added at runtime) still receive their configured values, as unmatched keys are retained until the CVar is registered.
 pub struct CVarLoaderPlugin {
-    /// The user's config file within the OS filesystem.
+    /// Where the user's config is loaded from and saved to.
+    user_config: Option<Arc<dyn CVarStorage>>,
+    /// How long to wait after the last change to a saved CVar before saving the user's config, if it's saved automatically.
+    auto_save: Option<Duration>,
+    /// How the user's config is saved.
+    save_options: UserConfigSaveOptions,
+    /// The directory scripts are run from, including [AUTOEXEC].
     #[cfg(feature = "config_loader_fs")]
-    user_config_file: Option<PathBuf>,
+    script_directory: Option<PathBuf>,
     /// Any extra layers to load at startup.
     extra_layers: Vec<DocumentContext<String>>,
+    /// The asset directory to load the layers named by [ConfigLayers](crate::builtin::ConfigLayers) from.
+    #[cfg(feature = "config_loader_asset")]
+    asset_layer_root: Option<String>,
+    /// The features conditional sections can test for.
+    features: Vec<String>,
+    /// How keys that don't match any registered CVar are treated, for each kind of layer.
+    unknown_keys: HashMap<LayerKind, UnknownKeyPolicy>,
 }
 
-impl Plugin for CVarLoaderPlugin {
-    fn build(&self, app: &mut bevy_app::App) {
-        let loader = ConfigLoader::default();
-        // Begin with any extra layers.
+impl CVarLoaderPlugin {
+    /// Returns the loader to apply the given kind of layer with.
+    fn loader(&self, kind: LayerKind) -> ConfigLoader {
+        let policy = self.unknown_keys.get(&kind).copied().unwrap_or_default();
 
-        for layer in self.extra_layers.iter() {
-            let res = loader.apply(app.world_mut(), layer.clone(), false);
+        ConfigLoader::default().with_unknown_keys(policy)
+    }
+
+    /// Runs [AUTOEXEC] from the script directory as a [LayerKind::Script] layer, if it exists.
+    #[cfg(feature = "config_loader_fs")]
+    fn run_autoexec(&self, world: &mut World) {
+        let Some(directory) = self.script_directory.as_ref() else {
+            return;
+        };
+
+        let path = directory.join(AUTOEXEC);
 
-            if let Err(e) = res {
+        if !path.exists() {
+            return;
+        }
+
+        match CVarScript::load_file(&path) {
+            Ok(script) => {
+                // Failing commands are already logged, and don't stop the rest of the script from applying.
+                let (layer, _) = script.to_layer(world, LayerKind::Script);
+
+                crate::layers::add_layer(world, layer);
+            }
+            Err(e) => warn!("Failed to run {AUTOEXEC}, got error: {e}"),
+        }
+    }
+
+    /// Returns the debounce to save the user's config with, if auto-saving is enabled.
+    fn auto_save_debounce(&self) -> Option<Duration> {
+        self.auto_save.filter(|_| self.user_config.is_some())
+    }
+
+    /// Reads the user's config along with the configs it includes, see [CVarStorage::load_config].
+    fn read_user_config(&self) -> Vec<DocumentContext<String>> {
+        let Some(storage) = self.user_config.as_ref() else {
+            return vec![];
+        };
+
+        match storage.load_config() {
+            Ok(documents) => documents,
+            Err(e) => {
                 warn!(
-                    "Failed to load an extra layer ({}), got error: {}",
-                    layer.source(),
-                    e
+                    "Failed to load the user's config ({}), got error: {e}",
+                    storage.source()
                 );
+                vec![]
             }
         }
+    }
+}
+
+impl Plugin for CVarLoaderPlugin {
+    fn build(&self, app: &mut bevy_app::App) {
+        app.world_mut()
+            .get_resource_or_init::<ConfigFeatures>()
+            .0
+            .extend(self.features.iter().cloned());
+
+        if let Some(storage) = self.user_config.clone() {
+            app.insert_resource(UserConfigStorage(storage));
+        }
+
+        app.insert_resource(self.save_options);
 
         #[cfg(feature = "config_loader_fs")]
-        {
-            if let Some(ref path) = self.user_config_file {
-                let res = File::options()
-                    .read(true)
-                    .create(true)
-                    .append(true)
-                    .open(path);
-
-                if let Err(e) = res {
-                    warn!(
-                        "Failed to create or open the user config file at {path:?}, got error: {e}"
-                    );
-                } else if let Ok(mut file) = res {
-                    let mut buf = String::new();
-                    file.read_to_string(&mut buf).unwrap();
-
-                    let res = loader.apply_from_string(
-                        app.world_mut(),
-                        &buf,
-                        Some(&path.to_string_lossy()),
-                        true
-                    );
+        if let Some(directory) = self.script_directory.clone() {
+            app.insert_resource(ScriptDirectory(directory));
+        }
 
-                    if let Err(e) = res {
-                        warn!(
-                            "Failed to load the user's config file ({:?}), got error: {}",
-                            path, e
-                        );
-                    }
+        if self.auto_save_debounce().is_some() {
+            app.add_systems(Last, auto_save_user_config);
+        }
+    }
+
+    fn finish(&self, app: &mut bevy_app::App) {
+        // Layers are applied once every plugin is built, so every CVar has been registered by now.
+        let loader = self.loader(LayerKind::Embedded);
+        // Begin with any extra layers.
+
+        for layer in self.extra_layers.iter() {
+            let error = match loader.apply(app.world_mut(), layer.clone(), false) {
+                Ok(report) if report.is_ok() => continue,
+                Ok(report) => report.failures(),
+                Err(e) => e.to_string(),
+            };
+
+            warn!(
+                "Failed to load an extra layer ({}), got error: {error}",
+                layer.source()
+            );
+        }
+
+        // Scripts sit above the user's config, so it doesn't matter that asset layers (and the user's config) may be applied later.
+        #[cfg(feature = "config_loader_fs")]
+        self.run_autoexec(app.world_mut());
+
+        let user_config = self.read_user_config();
+
+        // Asset layers take a while to load, so the user's config is applied once they're done to keep it on top.
+        #[cfg(feature = "config_loader_asset")]
+        if let Some(ref root) = self.asset_layer_root {
+            if startup::can_load_layers(app) {
+                startup::begin_loading_layers(
+                    app,
+                    root,
+                    self.loader(LayerKind::Asset),
+                    user_config,
+                    self.loader(LayerKind::User),
+                );
+
+                // The asset layers and the user's config are applied once loaded, so only later changes are saved.
+                if let Some(debounce) = self.auto_save_debounce() {
+                    app.add_systems(
+                        bevy_state::state::OnEnter(CVarLoadState::Ready),
+                        move |world: &mut World| start_auto_save(world, debounce),
+                    );
                 }
+                return;
             }
+
+            warn!(
+                "Loading asset layers from {root} requires the AssetPlugin and StatesPlugin, only the user's config is loaded."
+            );
+        }
+
+        for user_config in user_config {
+            let source = user_config.source().to_owned();
+
+            let error = match self
+                .loader(LayerKind::User)
+                .apply(app.world_mut(), user_config, true)
+            {
+                Ok(report) if report.is_ok() => continue,
+                Ok(report) => report.failures(),
+                Err(e) => e.to_string(),
+            };
+
+            warn!("Failed to load the user's config file ({source}), got error: {error}");
+        }
+
+        if let Some(debounce) = self.auto_save_debounce() {
+            start_auto_save(app.world_mut(), debounce);
         }
     }
 }
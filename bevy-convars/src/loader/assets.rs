@@ -1,42 +1,61 @@
-use bevy_asset::{Asset, AssetLoader, Assets, AsyncReadExt as _, Handle};
+use bevy_asset::{
+    Asset, AssetLoader, AsyncReadExt as _, Handle, ReadAssetBytesError, io::AssetReaderError,
+};
 use bevy_ecs::world::World;
 use bevy_reflect::Reflect;
-use std::error::Error;
-use toml_edit::ImDocument;
+use std::{error::Error, marker::PhantomData};
 
-use crate::CVarError;
+use crate::{
+    CVarError,
+    format::{ConfigFormat, TomlFormat},
+};
 
-use super::{ConfigLoader, DocumentContext};
+use super::{
+    ConfigLoadReport, ConfigLoader, ConfigLoaderError, DocumentContext,
+    hot_reload::apply_config_asset, include::IncludeStack,
+};
 
-impl ConfigLoader {
-    /// Applies a given config to the world.
+impl<F: ConfigFormat> ConfigLoader<F> {
+    /// Applies a given config to the world, as a [LayerKind::Asset](crate::layers::LayerKind::Asset) layer (plus one for each config it includes).
+    ///
+    /// Returns a report of every key applied, skipped or failed to apply, or [CVarError::AssetNotLoaded] if the asset hasn't finished loading yet.
+    /// # Remarks
+    /// Applying the same asset again replaces its layer, only updating the CVars whose values changed.
+    /// Unknown keys are reported according to [ConfigLoader::unknown_keys], both now and when the asset is reloaded.
+    /// See [CVarConfigHotReloadPlugin](super::CVarConfigHotReloadPlugin) for doing so automatically.
     pub fn apply_asset(
         &self,
         world: &mut World,
         asset: Handle<CVarConfig>,
-    ) -> Result<(), CVarError> {
-        let document = world
-            .resource::<Assets<CVarConfig>>()
-            .get(&asset)
-            .unwrap()
-            .clone()
-            .0;
-
-        self.apply(world, document, false)?;
-
-        Ok(())
+    ) -> Result<ConfigLoadReport, CVarError> {
+        // Assets are parsed by their asset loader, so the loader's own format doesn't matter here.
+        apply_config_asset(world, asset.id(), self.with_format())
     }
 }
 
 /// A config as an asset, allowing you to use standard bevy asset loading.
+///
+/// Holds the config along with every config it includes, in the order they're applied.
 #[derive(Asset, Reflect, Clone)]
-pub struct CVarConfig(#[reflect(ignore)] pub(super) DocumentContext<String>);
+pub struct CVarConfig(#[reflect(ignore)] pub(super) Vec<DocumentContext<String>>);
 
-/// Provides an asset loader for [CVarConfig].
+impl CVarConfig {
+    /// Returns the config and every config it includes, in the order they're applied.
+    pub fn documents(&self) -> &[DocumentContext<String>] {
+        &self.0
+    }
+}
+
+/// Provides an asset loader for [CVarConfig], reading configs in the given format.
+///
+/// The TOML loader is registered by the [CVarLoaderPlugin](super::CVarLoaderPlugin) when asset layers are enabled, along with those
+/// for any other format enabled through cargo features. Otherwise, register it with `app.init_asset_loader::<ConfigAssetLoader>()`.
 #[derive(Default)]
-pub struct ConfigAssetLoader {}
+pub struct ConfigAssetLoader<F = TomlFormat> {
+    format: PhantomData<F>,
+}
 
-impl AssetLoader for ConfigAssetLoader {
+impl<F: ConfigFormat> AssetLoader for ConfigAssetLoader<F> {
     type Asset = CVarConfig;
 
     type Settings = ();
@@ -51,13 +70,45 @@ impl AssetLoader for ConfigAssetLoader {
     ) -> Result<Self::Asset, Self::Error> {
         let mut buf = String::new();
         reader.read_to_string(&mut buf).await?;
-        Ok(CVarConfig(DocumentContext::new(
-            ImDocument::parse(buf)?,
-            load_context.path().to_str().unwrap().to_owned(),
-        )))
+
+        let path = load_context.asset_path().clone_owned();
+        let document = DocumentContext::parse::<F>(buf, path.to_string())?;
+
+        let mut stack = IncludeStack::new(path, document);
+
+        while let Some((parent, source, include)) = stack.next() {
+            let path = parent.resolve_embed(&include.path)?;
+
+            let bytes = match load_context.read_asset_bytes(&path).await {
+                Ok(bytes) => bytes,
+                Err(ReadAssetBytesError::AssetReaderError(AssetReaderError::NotFound(_)))
+                    if include.optional =>
+                {
+                    continue;
+                }
+                Err(e) => {
+                    return Err(Box::new(ConfigLoaderError::Include {
+                        path: path.to_string(),
+                        error: Box::new(e),
+                    }));
+                }
+            };
+
+            let document = String::from_utf8(bytes)
+                .map_err(|e| e.into())
+                .and_then(|x| DocumentContext::parse::<F>(x, path.to_string()))
+                .map_err(|error| ConfigLoaderError::Include {
+                    path: path.to_string(),
+                    error,
+                })?;
+
+            stack.push(path.clone(), document.with_included_from(source))?;
+        }
+
+        Ok(CVarConfig(stack.finish()))
     }
 
     fn extensions(&self) -> &[&str] {
-        &["toml"]
+        F::EXTENSIONS
     }
 }
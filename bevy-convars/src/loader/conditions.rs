@@ -0,0 +1,139 @@
+use std::str::FromStr;
+
+use bevy_ecs::{reflect::AppTypeRegistry, resource::Resource, world::World};
+use bevy_platform::collections::HashSet;
+use serde::de::IntoDeserializer;
+use toml_edit::Value;
+
+use crate::{CVarManagement, layers::same_value, save::serialize_value};
+
+/// The root key holding conditional sections, i.e. `[when.debug_assertions]` or `[when."render.backend == 'gl'"]`.
+///
+/// Each section is keyed by a condition, and is laid out like the root of the config. Sections whose condition holds are
+/// merged on top of the rest of the config in the order they're written, the others are skipped.
+///
+/// Conditions are one of:
+/// - `debug_assertions`, which holds for builds with debug assertions enabled.
+/// - `target_os == '...'` or `target_family == '...'`, compared against [std::env::consts::OS] and [std::env::consts::FAMILY].
+/// - `feature == '...'`, which holds if the feature was enabled through [ConfigFeatures].
+/// - `path.to.cvar == value` or `path.to.cvar != value`, comparing a CVar's current value to a TOML value read as the CVar's type.
+/// - `path.to.cvar`, which holds if a boolean CVar is true.
+///
+/// Any condition can be negated by prefixing it with `!`.
+///
+/// # Remarks
+/// Conditions on CVars are evaluated against the CVar's value when the config is applied, before any of the config's own values are.
+pub const WHEN_KEY: &str = "when";
+
+/// The features conditional sections can test for with `feature == '...'`, see [WHEN_KEY].
+///
+/// Usually populated from the app's own cargo features, i.e. through
+/// [CVarLoaderPluginBuilder::with_feature](super::CVarLoaderPluginBuilder::with_feature).
+#[derive(Resource, Default, Debug, Clone)]
+pub struct ConfigFeatures(pub HashSet<String>);
+
+/// Evaluates a condition, returning whether it holds along with a description of why.
+/// Returns an error describing the problem if the condition is malformed.
+pub(crate) fn evaluate(world: &World, condition: &str) -> Result<(bool, String), String> {
+    let condition = condition.trim();
+
+    if let Some(inner) = condition.strip_prefix('!') {
+        return evaluate(world, inner).map(|(holds, reason)| (!holds, reason));
+    }
+
+    let comparison = [("==", false), ("!=", true)]
+        .into_iter()
+        .filter_map(|(op, negated)| condition.find(op).map(|x| (x, op, negated)))
+        .min_by_key(|x| x.0);
+
+    let Some((index, op, negated)) = comparison else {
+        return evaluate_flag(world, condition);
+    };
+
+    let lhs = condition[..index].trim();
+    let rhs = condition[index + op.len()..].trim();
+    let rhs = Value::from_str(rhs).map_err(|e| format!("invalid value {rhs}: {e}"))?;
+
+    let (holds, reason) = compare(world, lhs, &rhs)?;
+
+    Ok((holds != negated, reason))
+}
+
+/// Evaluates a condition without a comparison.
+fn evaluate_flag(world: &World, condition: &str) -> Result<(bool, String), String> {
+    if condition == "debug_assertions" {
+        let holds = cfg!(debug_assertions);
+
+        return Ok((holds, format!("debug_assertions is {holds}")));
+    }
+
+    let value = cvar_value(world, condition)?;
+    let holds = value
+        .as_bool()
+        .ok_or_else(|| format!("{condition} isn't a boolean CVar"))?;
+
+    Ok((holds, format!("{condition} is {holds}")))
+}
+
+/// Compares the left hand side of a condition to a value.
+fn compare(world: &World, lhs: &str, rhs: &Value) -> Result<(bool, String), String> {
+    let expected = || {
+        rhs.as_str()
+            .ok_or_else(|| format!("{lhs} can only be compared to a string"))
+    };
+
+    match lhs {
+        "target_os" => {
+            let holds = expected()? == std::env::consts::OS;
+
+            Ok((holds, format!("target_os is {:?}", std::env::consts::OS)))
+        }
+        "target_family" => {
+            let holds = expected()? == std::env::consts::FAMILY;
+
+            Ok((
+                holds,
+                format!("target_family is {:?}", std::env::consts::FAMILY),
+            ))
+        }
+        "feature" => {
+            let feature = expected()?;
+            let holds = world
+                .get_resource::<ConfigFeatures>()
+                .is_some_and(|x| x.0.contains(feature));
+
+            let state = if holds { "enabled" } else { "not enabled" };
+
+            Ok((holds, format!("feature {feature:?} is {state}")))
+        }
+        path => {
+            let value = cvar_value(world, path)?;
+            let management = world.resource::<CVarManagement>();
+
+            // Compared as the CVar's type, so `1` matches a float CVar holding `1.0`.
+            let expected = management
+                .deserialize_cvar(world, path, rhs.clone().into_deserializer())
+                .map_err(|e| format!("can't compare {path} to {rhs}: {e}"))?;
+
+            let holds = management
+                .get_cvar_reflect(world, path)
+                .ok()
+                .and_then(|x| x.reflect_partial_eq(expected.as_partial_reflect()))
+                .unwrap_or_else(|| same_value(&value, rhs));
+
+            Ok((holds, format!("{path} is {}", value.to_string().trim())))
+        }
+    }
+}
+
+/// Returns the current value of a CVar as a TOML value.
+fn cvar_value(world: &World, path: &str) -> Result<Value, String> {
+    let value = world
+        .resource::<CVarManagement>()
+        .get_cvar_reflect(world, path)
+        .map_err(|e| format!("couldn't read {path}: {e}"))?;
+    let registry = world.resource::<AppTypeRegistry>().read();
+
+    serialize_value(&registry, value.as_partial_reflect())
+        .map_err(|e| format!("couldn't read {path}: {e}"))
+}
@@ -1,12 +1,41 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bevy_ecs::world::World;
 use toml_edit::{ImDocument, Item, Table};
 
-use crate::{CVarFlags, CVarManagement, CVarTreeNode, reflect::ReflectCVar};
+use crate::{
+    CVarFlags, CVarManagement, CVarTreeNode,
+    format::{ConfigFormat, FormatError},
+    layers::SourceLocation,
+    reflect::ReflectCVar,
+};
+
+#[cfg(any(feature = "config_loader_fs", feature = "config_loader_asset"))]
+use super::include::Include;
+use super::{
+    ORPHAN_METADATA_KEY,
+    conditions::{WHEN_KEY, evaluate},
+    include::INCLUDE_KEY,
+    report::{ConfigLoadReport, ReportEntry, SkipReason},
+};
+
+pub(crate) type UnparsedCVar = (&'static str, Item, Option<SourceLocation>);
+pub(crate) type UnmatchedCVar = (String, Item, Option<SourceLocation>);
 
-pub(crate) type UnparsedCVar<'a> = (&'a str, Item);
+/// The CVars found by a [CVarDocScanner], along with the keys that didn't match any registered CVar and those that were skipped.
+#[derive(Default)]
+pub(crate) struct ScannedDocument {
+    pub cvars: Vec<UnparsedCVar>,
+    pub unmatched: Vec<UnmatchedCVar>,
+    /// Keys that didn't match any registered CVar but aren't retained, i.e. those in conditional sections of the user config.
+    pub unretained: Vec<UnmatchedCVar>,
+    pub report: ConfigLoadReport,
+}
 
 pub(crate) struct CVarDocScanner<S: AsRef<str>> {
     document: ImDocument<S>,
     source: String,
+    spans: bool,
     user_config: bool,
 }
 
@@ -15,6 +44,9 @@ pub(crate) struct CVarDocScanner<S: AsRef<str>> {
 pub struct DocumentContext<S: AsRef<str>> {
     document: ImDocument<S>,
     source: String,
+    included_from: Option<String>,
+    /// Whether positions within the document point into its source, see [ConfigFormat::SPANS].
+    spans: bool,
 }
 
 impl Default for DocumentContext<String> {
@@ -22,6 +54,8 @@ impl Default for DocumentContext<String> {
         Self {
             document: ImDocument::parse(String::new()).unwrap(),
             source: Default::default(),
+            included_from: None,
+            spans: true,
         }
     }
 }
@@ -29,13 +63,78 @@ impl Default for DocumentContext<String> {
 impl<S: AsRef<str>> DocumentContext<S> {
     /// Creates a new DocumentContext.
     pub fn new(document: ImDocument<S>, source: String) -> Self {
-        Self { document, source }
+        Self {
+            document,
+            source,
+            included_from: None,
+            spans: true,
+        }
+    }
+
+    /// Returns the underlying TOML document.
+    pub fn document(&self) -> &ImDocument<S> {
+        &self.document
     }
 
     /// Returns the source of this document.
     pub fn source(&self) -> &str {
         &self.source
     }
+
+    /// Returns the source of the document that included this one, if it was loaded through an [INCLUDE_KEY] entry.
+    pub fn included_from(&self) -> Option<&str> {
+        self.included_from.as_deref()
+    }
+
+    /// Marks this document as included by the document with the given source.
+    #[cfg(any(feature = "config_loader_fs", feature = "config_loader_asset"))]
+    pub(crate) fn with_included_from(self, source: String) -> Self {
+        Self {
+            included_from: Some(source),
+            ..self
+        }
+    }
+
+    /// Returns the configs this document includes, in the order they're listed.
+    #[cfg(any(feature = "config_loader_fs", feature = "config_loader_asset"))]
+    pub(crate) fn includes(&self) -> Vec<Include> {
+        let Some(item) = self.document.get(INCLUDE_KEY) else {
+            return vec![];
+        };
+
+        let Some(array) = item.as_array() else {
+            bevy_log::warn!(
+                "When parsing {}, found an {INCLUDE_KEY} key that was expected to be an array. Was of type {}",
+                self.source,
+                item.type_name()
+            );
+            return vec![];
+        };
+
+        array
+            .iter()
+            .filter_map(|x| match x.as_str() {
+                Some(entry) => Some(Include::parse(entry)),
+                None => {
+                    bevy_log::warn!(
+                        "When parsing {}, found an {INCLUDE_KEY} entry that wasn't a string.",
+                        self.source
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+impl DocumentContext<String> {
+    /// Parses a config in the given format, see [ConfigFormat].
+    pub fn parse<F: ConfigFormat>(text: String, source: String) -> Result<Self, FormatError> {
+        Ok(Self {
+            spans: F::SPANS,
+            ..Self::new(F::parse(text)?, source)
+        })
+    }
 }
 
 impl<S: AsRef<str>> CVarDocScanner<S> {
@@ -43,59 +142,226 @@ impl<S: AsRef<str>> CVarDocScanner<S> {
         Self {
             document: document.document,
             source: document.source,
-            user_config: user_config,
+            spans: document.spans,
+            user_config,
         }
     }
 
-    /// Recursively traverse a TOML document for CVars.
+    /// Recursively traverse a TOML document for CVars, collecting any keys that don't belong to a registered CVar.
     fn traverse(
         &self,
         item: &Table,
+        prefix: &str,
         management: &CVarManagement,
         tree: &CVarTreeNode,
-        outp: &mut Vec<UnparsedCVar<'_>>,
+        scanned: &mut ScannedDocument,
     ) {
-        for (key, node) in tree.children().unwrap() {
-            // Check if the node key exists within the document we're traversing, and if so get the value.
-            if let Some((_, value)) = item.get_key_value(key) {
-                if node.is_leaf() {
-                    let CVarTreeNode::Leaf { name, reg } = node else {
-                        unreachable!()
-                    };
-
-                    let meta = management.resources[reg].data::<ReflectCVar>().unwrap();
-
-                    if meta.flags().contains(CVarFlags::SAVED) || !self.user_config {
-                        outp.push((*name, value.clone()));
-                    } else {
-                        bevy_log::warn!(
-                            "Found cvar {name} in {}, but that CVar cannot be saved (and as such cannot be loaded.)",
-                            self.source
-                        );
-                    }
-                } else if let Some(item) = value.as_table() {
-                    self.traverse(item, management, node, outp);
+        for (key, value) in item.iter() {
+            let path = if prefix.is_empty() {
+                key.to_owned()
+            } else {
+                format!("{prefix}.{key}")
+            };
+
+            // The user config keeps track of its orphaned keys, which isn't a CVar.
+            if self.user_config && prefix.is_empty() && key == ORPHAN_METADATA_KEY {
+                continue;
+            }
+
+            // Includes are resolved when the document is loaded, and conditional sections once the rest is scanned.
+            if prefix.is_empty() && (key == INCLUDE_KEY || key == WHEN_KEY) {
+                continue;
+            }
+
+            let location = self.location(item, key, value);
+
+            // Check if the document key exists within the tree, and if so get the node.
+            let Some(node) = tree.child(key) else {
+                self.collect_unmatched(path, value, location, &mut scanned.unmatched);
+                continue;
+            };
+
+            if node.is_leaf() {
+                let CVarTreeNode::Leaf { name, reg } = node else {
+                    unreachable!()
+                };
+
+                let meta = management.resources[reg].data::<ReflectCVar>().unwrap();
+
+                if meta.flags().contains(CVarFlags::SAVED) || !self.user_config {
+                    scanned.cvars.push((*name, value.clone(), location));
                 } else {
                     bevy_log::warn!(
-                        "When parsing {}, found a cvar-like key {key} that was expected to be a table. Was of type {}",
-                        self.source,
-                        value.type_name()
+                        "Found cvar {name} in {}, but that CVar cannot be saved (and as such cannot be loaded.)",
+                        self.source
+                    );
+                    scanned.report.skip(
+                        ReportEntry::new(path, &self.source, location),
+                        SkipReason::NotSaved,
                     );
                 }
+            } else if let Some(item) = value.as_table() {
+                self.traverse(item, &path, management, node, scanned);
+            } else {
+                bevy_log::warn!(
+                    "When parsing {}, found a cvar-like key {key} that was expected to be a table. Was of type {}",
+                    self.source,
+                    value.type_name()
+                );
+                scanned.report.skip(
+                    ReportEntry::new(path, &self.source, location),
+                    SkipReason::ExpectedTable,
+                );
             }
         }
     }
 
-    pub fn find_cvars(&self, management: &CVarManagement) -> Vec<UnparsedCVar<'_>> {
-        let mut outp = vec![];
+    /// Flattens standard tables into their individual keys, as any of them may turn out to be a CVar.
+    fn collect_unmatched(
+        &self,
+        path: String,
+        value: &Item,
+        location: Option<SourceLocation>,
+        unmatched: &mut Vec<UnmatchedCVar>,
+    ) {
+        if let Some(table) = value.as_table() {
+            for (key, value) in table.iter() {
+                let location = self.location(table, key, value);
+
+                self.collect_unmatched(format!("{path}.{key}"), value, location, unmatched);
+            }
+        } else {
+            unmatched.push((path, value.clone(), location));
+        }
+    }
+
+    /// Returns where the given key of a table was written within the document.
+    fn location(&self, table: &Table, key: &str, value: &Item) -> Option<SourceLocation> {
+        if !self.spans {
+            return None;
+        }
+
+        let span = table
+            .key(key)
+            .and_then(|x| x.span())
+            .or_else(|| value.span())?;
+
+        Some(SourceLocation::from_offset(self.document.raw(), span.start))
+    }
+
+    /// Returns when the given key was first found orphaned, as recorded in the user config.
+    pub fn orphaned_since(&self, path: &str) -> Option<SystemTime> {
+        let secs = self
+            .document
+            .get(ORPHAN_METADATA_KEY)?
+            .as_table_like()?
+            .get(path)?
+            .as_integer()?;
+
+        Some(UNIX_EPOCH + Duration::from_secs(secs.try_into().ok()?))
+    }
+
+    /// Scans the document, returning both the CVars found and the keys that didn't match any registered CVar.
+    /// Conditional sections that hold are merged on top of the rest of the document in order, see [WHEN_KEY].
+    pub fn scan(&self, world: &World) -> ScannedDocument {
+        let management = world.resource::<CVarManagement>();
+
+        let mut scanned = ScannedDocument::default();
 
         self.traverse(
             self.document.as_table(),
+            "",
             management,
             &management.tree,
-            &mut outp,
+            &mut scanned,
         );
 
-        outp
+        for (condition, section) in self.conditional_sections() {
+            let reason = match evaluate(world, condition) {
+                Ok((true, reason)) => {
+                    bevy_log::debug!(
+                        "Applying [{WHEN_KEY}.{condition:?}] in {}, as {reason}.",
+                        self.source
+                    );
+                    None
+                }
+                Ok((false, reason)) => {
+                    bevy_log::info!(
+                        "Skipping [{WHEN_KEY}.{condition:?}] in {}, as {reason}.",
+                        self.source
+                    );
+                    Some(reason)
+                }
+                Err(e) => {
+                    bevy_log::warn!(
+                        "Skipping [{WHEN_KEY}.{condition:?}] in {}, as the condition couldn't be evaluated: {e}",
+                        self.source
+                    );
+                    Some(format!("the condition couldn't be evaluated: {e}"))
+                }
+            };
+
+            if let Some(reason) = reason {
+                let mut keys = vec![];
+
+                for (key, value) in section.iter() {
+                    let location = self.location(section, key, value);
+
+                    self.collect_unmatched(key.to_owned(), value, location, &mut keys);
+                }
+
+                for (path, _, location) in keys {
+                    scanned.report.skip(
+                        ReportEntry::new(path, &self.source, location),
+                        SkipReason::ConditionNotMet(reason.clone()),
+                    );
+                }
+
+                continue;
+            }
+
+            let unmatched = scanned.unmatched.len();
+
+            self.traverse(section, "", management, &management.tree, &mut scanned);
+
+            // Orphans are written back to the root of the user config, so unmatched keys are only kept for other configs.
+            if self.user_config {
+                let unretained = scanned.unmatched.split_off(unmatched);
+                scanned.unretained.extend(unretained);
+            }
+        }
+
+        scanned
+    }
+
+    /// Returns the conditional sections of the document, along with their conditions.
+    fn conditional_sections(&self) -> Vec<(&str, &Table)> {
+        let Some(item) = self.document.get(WHEN_KEY) else {
+            return vec![];
+        };
+
+        let Some(table) = item.as_table() else {
+            bevy_log::warn!(
+                "When parsing {}, found a {WHEN_KEY} key that was expected to be a table. Was of type {}",
+                self.source,
+                item.type_name()
+            );
+            return vec![];
+        };
+
+        table
+            .iter()
+            .filter_map(|(condition, section)| match section.as_table() {
+                Some(section) => Some((condition, section)),
+                None => {
+                    bevy_log::warn!(
+                        "When parsing {}, found a condition {condition:?} that was expected to be a table. Was of type {}",
+                        self.source,
+                        section.type_name()
+                    );
+                    None
+                }
+            })
+            .collect()
     }
 }
@@ -0,0 +1,158 @@
+use bevy_app::{Plugin, PreUpdate};
+use bevy_asset::{AssetEvent, AssetId, Assets};
+use bevy_ecs::{
+    event::EventReader,
+    resource::Resource,
+    schedule::IntoScheduleConfigs,
+    system::{Commands, Res},
+    world::World,
+};
+use bevy_log::warn;
+use bevy_platform::collections::HashMap;
+
+use crate::{
+    CVarError, CVarManagement,
+    layers::{LayerId, LayerKind, add_layer, remove_layer, replace_layer},
+};
+
+use super::{CVarConfig, CVarLoadSet, ConfigLoadReport, ConfigLoader, pending::store_pending};
+
+/// A config asset that was applied, along with the loader it was applied with.
+pub(crate) struct AppliedConfigAsset {
+    layers: Vec<LayerId>,
+    loader: ConfigLoader,
+}
+
+/// Tracks the layers each config asset was applied as, so they can be replaced when the asset changes.
+#[derive(Resource, Default)]
+pub(crate) struct AppliedConfigAssets(HashMap<AssetId<CVarConfig>, AppliedConfigAsset>);
+
+/// Applies the given config asset (and the configs it includes) to the world as [LayerKind::Asset] layers,
+/// replacing the layers if it was applied before.
+/// Nothing is applied if any of the configs is rejected by the loader's [UnknownKeyPolicy](super::UnknownKeyPolicy).
+/// Returns a report covering every config applied.
+pub(crate) fn apply_config_asset(
+    world: &mut World,
+    id: AssetId<CVarConfig>,
+    loader: ConfigLoader,
+) -> Result<ConfigLoadReport, CVarError> {
+    let documents = world
+        .get_resource::<Assets<CVarConfig>>()
+        .and_then(|x| x.get(id))
+        .ok_or(CVarError::AssetNotLoaded)?
+        .clone()
+        .0;
+
+    let layers = world.resource::<CVarManagement>().layers();
+    let existing: Vec<LayerId> = world
+        .get_resource::<AppliedConfigAssets>()
+        .and_then(|x| x.0.get(&id))
+        .map(|x| x.layers.clone())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|x| layers.get(*x).is_some())
+        .collect();
+
+    // Layers are replaced in place while the asset includes the same configs, so only the values that changed are touched.
+    let replace = existing.len() == documents.len()
+        && existing
+            .iter()
+            .zip(documents.iter())
+            .all(|(x, document)| layers.get(*x).unwrap().source() == document.source());
+
+    let scanned = documents
+        .into_iter()
+        .map(|document| loader.scan_document(world, document, LayerKind::Asset))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Values from the previous version that are still waiting for their CVar may no longer be in the asset.
+    let mut management = world.resource_mut::<CVarManagement>();
+
+    for layer_id in existing.iter() {
+        management.clear_pending(*layer_id);
+    }
+
+    let mut layer_ids = vec![];
+    let mut report = ConfigLoadReport::default();
+
+    for (i, (layer, pending, mut layer_report)) in scanned.into_iter().enumerate() {
+        let source = layer.source().to_owned();
+
+        let (layer_id, failures) = if replace {
+            let layer_id = existing[i];
+
+            (
+                layer_id,
+                replace_layer(world, layer_id, layer).unwrap_or_default(),
+            )
+        } else {
+            add_layer(world, layer)
+        };
+
+        store_pending(world, layer_id, pending);
+
+        layer_report.record_failures(world.resource::<CVarManagement>(), &source, failures);
+
+        layer_ids.push(layer_id);
+        report.extend(layer_report);
+    }
+
+    // Otherwise the new layers are added before the old ones are removed, so CVars go straight to their new values.
+    if !replace {
+        for layer_id in existing {
+            remove_layer(world, layer_id);
+        }
+    }
+
+    world
+        .get_resource_or_init::<AppliedConfigAssets>()
+        .0
+        .insert(
+            id,
+            AppliedConfigAsset {
+                layers: layer_ids,
+                loader,
+            },
+        );
+
+    Ok(report)
+}
+
+/// Watches for modified [CVarConfig] assets, and reapplies those that were previously applied with [ConfigLoader::apply_asset].
+fn reload_config_assets(
+    mut events: EventReader<AssetEvent<CVarConfig>>,
+    applied: Res<AppliedConfigAssets>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let AssetEvent::Modified { id } = *event else {
+            continue;
+        };
+
+        let Some(loader) = applied.0.get(&id).map(|x| x.loader) else {
+            continue;
+        };
+
+        commands.queue(move |world: &mut World| {
+            if let Err(e) = apply_config_asset(world, id, loader) {
+                warn!("Failed to reload config asset {id}, got error: {e}");
+            }
+        });
+    }
+}
+
+/// Plugin that reapplies [CVarConfig] assets when they're modified, i.e. by Bevy's asset watcher.
+///
+/// Only keys whose values changed in the asset are updated, and keys removed from the asset fall back
+/// to the value of the next layer down, or the default (see [layers](crate::layers)).
+///
+/// # Remarks
+/// Only assets applied through [ConfigLoader::apply_asset] are reloaded.
+pub struct CVarConfigHotReloadPlugin;
+
+impl Plugin for CVarConfigHotReloadPlugin {
+    fn build(&self, app: &mut bevy_app::App) {
+        app.init_resource::<AppliedConfigAssets>();
+        app.add_systems(PreUpdate, reload_config_assets.in_set(CVarLoadSet));
+    }
+}
@@ -0,0 +1,117 @@
+#[cfg(any(feature = "config_loader_fs", feature = "config_loader_asset"))]
+use super::{ConfigLoaderError, DocumentContext};
+
+/// The root key listing the configs a config includes.
+///
+/// Each entry is a path relative to the including config, optionally prefixed with `?` to skip it if it doesn't exist.
+/// Included configs are applied before the config including them (as separate layers), so the including config takes priority.
+pub const INCLUDE_KEY: &str = "include";
+
+/// A config listed under [INCLUDE_KEY].
+#[cfg(any(feature = "config_loader_fs", feature = "config_loader_asset"))]
+pub(crate) struct Include {
+    /// The path of the config, relative to the including config.
+    pub path: String,
+    /// Whether the config is skipped if it doesn't exist, rather than failing.
+    pub optional: bool,
+}
+
+#[cfg(any(feature = "config_loader_fs", feature = "config_loader_asset"))]
+impl Include {
+    pub fn parse(entry: &str) -> Self {
+        match entry.strip_prefix('?') {
+            Some(path) => Self {
+                path: path.to_owned(),
+                optional: true,
+            },
+            None => Self {
+                path: entry.to_owned(),
+                optional: false,
+            },
+        }
+    }
+}
+
+/// A config being walked, along with its path and the includes left to load.
+#[cfg(any(feature = "config_loader_fs", feature = "config_loader_asset"))]
+struct IncludeFrame<P> {
+    path: P,
+    document: DocumentContext<String>,
+    includes: std::vec::IntoIter<Include>,
+}
+
+/// Walks the includes of a config depth-first, producing every config in the order they should be applied.
+#[cfg(any(feature = "config_loader_fs", feature = "config_loader_asset"))]
+pub(crate) struct IncludeStack<P> {
+    frames: Vec<IncludeFrame<P>>,
+    /// The configs whose includes have all been loaded.
+    loaded: Vec<DocumentContext<String>>,
+}
+
+#[cfg(any(feature = "config_loader_fs", feature = "config_loader_asset"))]
+impl<P: PartialEq> IncludeStack<P> {
+    pub fn new(path: P, document: DocumentContext<String>) -> Self {
+        Self {
+            frames: vec![IncludeFrame::new(path, document)],
+            loaded: vec![],
+        }
+    }
+
+    /// Returns the next include to load, along with the path and source of the config including it.
+    pub fn next(&mut self) -> Option<(&P, String, Include)> {
+        loop {
+            let frame = self.frames.last_mut()?;
+
+            if let Some(include) = frame.includes.next() {
+                let frame = self.frames.last().unwrap();
+
+                return Some((&frame.path, frame.document.source().to_owned(), include));
+            }
+
+            let frame = self.frames.pop().unwrap();
+            self.loaded.push(frame.document);
+        }
+    }
+
+    /// Adds a loaded include, to be walked next.
+    pub fn push(
+        &mut self,
+        path: P,
+        document: DocumentContext<String>,
+    ) -> Result<(), ConfigLoaderError> {
+        if let Some(start) = self.frames.iter().position(|x| x.path == path) {
+            let cycle = self.frames[start..]
+                .iter()
+                .map(|x| x.document.source())
+                .chain(std::iter::once(document.source()))
+                .collect::<Vec<_>>()
+                .join(" -> ");
+
+            return Err(ConfigLoaderError::IncludeCycle(cycle));
+        }
+
+        self.frames.push(IncludeFrame::new(path, document));
+
+        Ok(())
+    }
+
+    /// Returns every config in the order they should be applied, once [IncludeStack::next] returns `None`.
+    pub fn finish(self) -> Vec<DocumentContext<String>> {
+        debug_assert!(self.frames.is_empty());
+
+        self.loaded
+    }
+}
+
+#[cfg(any(feature = "config_loader_fs", feature = "config_loader_asset"))]
+impl<P> IncludeFrame<P> {
+    fn new(path: P, document: DocumentContext<String>) -> Self {
+        let includes = document.includes().into_iter();
+
+        Self {
+            path,
+            document,
+            includes,
+        }
+    }
+}
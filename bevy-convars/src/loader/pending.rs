@@ -0,0 +1,210 @@
+use std::time::SystemTime;
+
+use bevy_ecs::world::World;
+use bevy_log::warn;
+use serde::de::IntoDeserializer;
+use toml_edit::{InlineTable, Value};
+
+use crate::{
+    CVarFlags, CVarManagement, WorldExtensions,
+    layers::{LayerId, SourceLocation},
+    reflect::ReflectCVar,
+};
+
+/// A config value that didn't match any registered CVar when its layer was applied.
+/// It's kept around and applied if a CVar with its path is registered later on.
+#[derive(Debug, Clone)]
+pub struct PendingCVar {
+    /// The value found in the config.
+    pub value: Value,
+    /// The source of the layer the value came from.
+    pub source: String,
+    /// Whether the value came from the user's config.
+    pub user_config: bool,
+    /// When the value was first found without a matching CVar, for values from the user's config.
+    pub orphaned_since: Option<SystemTime>,
+    /// Where the value was written within its source, if known.
+    pub location: Option<SourceLocation>,
+    /// The layer the value came from, which it's added to once the CVar is registered.
+    pub(crate) layer: Option<LayerId>,
+}
+
+impl CVarManagement {
+    /// Returns all config values that are waiting for their CVar to be registered, keyed by path.
+    pub fn pending_cvars(&self) -> impl Iterator<Item = (&str, &PendingCVar)> {
+        self.pending.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Returns the pending values that came from the user's config, keyed by path.
+    /// These are orphans, usually belonging to a disabled mod, and are written back when saving the user config so they aren't lost.
+    pub fn orphans(&self) -> impl Iterator<Item = (&str, &PendingCVar)> {
+        self.pending_cvars().filter(|(_, x)| x.user_config)
+    }
+
+    /// Purges the orphan at the given path, returning it. It'll be removed from the user config when next saved.
+    pub fn purge_orphan(&mut self, path: &str) -> Option<PendingCVar> {
+        if !self.pending.get(path)?.user_config {
+            return None;
+        }
+
+        self.purged_orphans.insert(path.to_owned());
+        self.pending.remove(path)
+    }
+
+    /// Purges every orphan matching the filter, returning how many were purged. They'll be removed from the user config when next saved.
+    pub fn purge_orphans(&mut self, mut filter: impl FnMut(&str, &PendingCVar) -> bool) -> usize {
+        let paths: Vec<String> = self
+            .orphans()
+            .filter(|(path, pending)| filter(path, pending))
+            .map(|(path, _)| path.to_owned())
+            .collect();
+
+        for path in paths.iter() {
+            self.purge_orphan(path);
+        }
+
+        paths.len()
+    }
+
+    /// Stores a config value for a CVar that isn't registered yet. Later layers override earlier ones.
+    pub(crate) fn add_pending(&mut self, path: String, pending: PendingCVar) {
+        if pending.user_config {
+            self.purged_orphans.remove(&path);
+        }
+
+        self.pending.insert(path, pending);
+    }
+
+    /// Drops the pending values that came from the given layer, i.e. before it's replaced by a new version of its config.
+    #[cfg(feature = "config_loader_asset")]
+    pub(crate) fn clear_pending(&mut self, layer: LayerId) {
+        self.pending.retain(|_, x| x.layer != Some(layer));
+    }
+
+    /// Takes the pending value for a path, assembling it into a table if it was written as individual keys.
+    pub(crate) fn take_pending(&mut self, path: &str) -> Option<PendingCVar> {
+        if let Some(pending) = self.pending.remove(path) {
+            return Some(pending);
+        }
+
+        let prefix = format!("{path}.");
+        let mut keys: Vec<String> = self
+            .pending
+            .keys()
+            .filter(|x| x.starts_with(&prefix))
+            .cloned()
+            .collect();
+
+        if keys.is_empty() {
+            return None;
+        }
+
+        keys.sort();
+
+        let mut table = InlineTable::new();
+        let mut sources: Vec<String> = vec![];
+        let mut user_config = false;
+        let mut orphaned_since: Option<SystemTime> = None;
+        let mut location: Option<SourceLocation> = None;
+        let mut layer = None;
+
+        for key in keys {
+            let pending = self.pending.remove(&key).unwrap();
+
+            let mut segments = key[prefix.len()..].split('.').peekable();
+            let mut target = &mut table;
+
+            while let Some(segment) = segments.next() {
+                if segments.peek().is_none() {
+                    target.insert(segment, pending.value.clone());
+                } else {
+                    let entry = target
+                        .entry(segment)
+                        .or_insert_with(|| Value::InlineTable(InlineTable::new()));
+
+                    if !entry.is_inline_table() {
+                        *entry = Value::InlineTable(InlineTable::new());
+                    }
+
+                    target = entry.as_inline_table_mut().unwrap();
+                }
+            }
+
+            if !sources.contains(&pending.source) {
+                sources.push(pending.source);
+            }
+
+            user_config |= pending.user_config;
+            layer = layer.max(pending.layer);
+            location = match (location, pending.location) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            orphaned_since = match (orphaned_since, pending.orphaned_since) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+        }
+
+        Some(PendingCVar {
+            value: Value::InlineTable(table),
+            source: sources.join(", "),
+            user_config,
+            orphaned_since,
+            location,
+            layer,
+        })
+    }
+}
+
+/// Stores the keys that didn't match any registered CVar, to be added to their layer once the CVar is registered.
+pub(crate) fn store_pending(world: &mut World, id: LayerId, pending: Vec<(String, PendingCVar)>) {
+    let mut management = world.resource_mut::<CVarManagement>();
+
+    for (path, mut pending) in pending {
+        pending.layer = Some(id);
+        management.add_pending(path, pending);
+    }
+}
+
+/// Applies any pending config value for the given path (which may be an alias of `cvar`), now that its CVar has been registered.
+/// The value is added to the layer it came from, so it takes part in resolution like any other.
+pub(crate) fn apply_pending_cvar(world: &mut World, cvar: &'static str, path: &str) {
+    let mut management = world.resource_mut::<CVarManagement>();
+
+    let Some(pending) = management.take_pending(path) else {
+        return;
+    };
+
+    let cid = management.tree.get(path).unwrap();
+    let flags = management.resources[&cid]
+        .data::<ReflectCVar>()
+        .unwrap()
+        .flags();
+
+    if pending.user_config && !flags.contains(CVarFlags::SAVED) {
+        warn!(
+            "Found cvar {path} in {}, but that CVar cannot be saved (and as such cannot be loaded.)",
+            pending.source
+        );
+        return;
+    }
+
+    if let Some(layer) = pending.layer.and_then(|x| management.layers.get_mut(x)) {
+        // Recomputed by the caller once every pending value is in place.
+        match pending.location {
+            Some(location) => layer.insert_with_location(cvar, pending.value, location),
+            None => layer.insert(cvar, pending.value),
+        }
+        return;
+    }
+
+    let res = world.set_cvar_deserialize(path, IntoDeserializer::into_deserializer(pending.value));
+
+    if let Err(e) = res {
+        warn!(
+            "Failed to apply the value for {path} from {} once it was registered, got error: {e}",
+            pending.source
+        );
+    }
+}
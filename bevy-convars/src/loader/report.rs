@@ -0,0 +1,190 @@
+use std::fmt::Display;
+
+use bevy_platform::collections::HashMap;
+
+use crate::{
+    CVarError, CVarManagement,
+    layers::{SourceLocation, canonical_path},
+};
+
+use super::UnknownKey;
+
+/// A key within a config, along with where it was written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportEntry {
+    /// The full path of the key. Keys matching a CVar through one of its aliases use the CVar's path.
+    pub path: String,
+    /// The source of the config containing the key, see [DocumentContext::source](super::DocumentContext::source).
+    pub source: String,
+    /// Where the key was written within the source, if known.
+    pub location: Option<SourceLocation>,
+}
+
+impl ReportEntry {
+    pub(crate) fn new(path: String, source: &str, location: Option<SourceLocation>) -> Self {
+        Self {
+            path,
+            source: source.to_owned(),
+            location,
+        }
+    }
+}
+
+impl Display for ReportEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)?;
+
+        if let Some(location) = self.location {
+            write!(f, ":{location}")?;
+        }
+
+        write!(f, ": {}", self.path)
+    }
+}
+
+/// Why a key in a config wasn't applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SkipReason {
+    /// The key doesn't match any registered CVar. Unless it's within a conditional section of the user's config,
+    /// it's retained and applied if a CVar with that path is registered later.
+    UnknownKey {
+        /// The registered CVar path closest to the key, if any is close enough to likely be what was meant.
+        suggestion: Option<&'static str>,
+    },
+    /// The key is a CVar in the user's config, but that CVar isn't [SAVED](crate::CVarFlags::SAVED) (and as such can't be loaded from it).
+    NotSaved,
+    /// The key's value couldn't be converted to a TOML value.
+    NotValueCompatible,
+    /// The key names a group of CVars, but wasn't a table.
+    ExpectedTable,
+    /// The key is within a conditional section that didn't hold (or couldn't be evaluated), for the given reason.
+    ConditionNotMet(String),
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::UnknownKey {
+                suggestion: Some(suggestion),
+            } => write!(f, "unknown key, did you mean {suggestion}?"),
+            SkipReason::UnknownKey { suggestion: None } => write!(f, "unknown key"),
+            SkipReason::NotSaved => write!(f, "CVar cannot be saved, and as such cannot be loaded"),
+            SkipReason::NotValueCompatible => write!(f, "value isn't value-compatible"),
+            SkipReason::ExpectedTable => write!(f, "expected a table"),
+            SkipReason::ConditionNotMet(reason) => write!(f, "skipped, as {reason}"),
+        }
+    }
+}
+
+/// What happened to each key of the configs applied by a [ConfigLoader](super::ConfigLoader), i.e. for showing the user which of their settings are broken.
+///
+/// Entries are sorted by source, then by where they were written.
+#[derive(Debug, Default)]
+pub struct ConfigLoadReport {
+    /// The keys that were applied to their layer. These may still be overridden by a higher priority layer.
+    pub applied: Vec<ReportEntry>,
+    /// The keys that weren't applied, along with why.
+    pub skipped: Vec<(ReportEntry, SkipReason)>,
+    /// The keys that were added to their layer, but whose value the CVar rejected.
+    pub failed: Vec<(ReportEntry, CVarError)>,
+}
+
+impl ConfigLoadReport {
+    /// Returns whether every key that matched a CVar was applied successfully.
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Merges another report into this one.
+    pub fn extend(&mut self, other: ConfigLoadReport) {
+        self.applied.extend(other.applied);
+        self.skipped.extend(other.skipped);
+        self.failed.extend(other.failed);
+
+        self.sort();
+    }
+
+    /// Describes the keys that failed to apply.
+    pub(crate) fn failures(&self) -> String {
+        self.failed
+            .iter()
+            .map(|(entry, error)| format!("{entry}: {error}"))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    pub(crate) fn skip(&mut self, entry: ReportEntry, reason: SkipReason) {
+        self.skipped.push((entry, reason));
+    }
+
+    pub(crate) fn skip_unknown(&mut self, unknown: Vec<UnknownKey>) {
+        for key in unknown {
+            let reason = SkipReason::UnknownKey {
+                suggestion: key.suggestion,
+            };
+
+            self.skip(
+                ReportEntry::new(key.path, &key.source, key.location),
+                reason,
+            );
+        }
+    }
+
+    /// Moves the applied keys whose CVar failed to apply over to the failed keys.
+    /// Failures for CVars not in the report (i.e. from a replaced layer) are recorded with the given source.
+    pub(crate) fn record_failures(
+        &mut self,
+        management: &CVarManagement,
+        source: &str,
+        failures: Vec<(String, CVarError)>,
+    ) {
+        let mut failures: HashMap<String, CVarError> = failures.into_iter().collect();
+
+        for entry in std::mem::take(&mut self.applied) {
+            let path = canonical_path(management, &entry.path).unwrap_or(entry.path.as_str());
+
+            match failures.remove(path) {
+                Some(error) => self.failed.push((entry, error)),
+                None => self.applied.push(entry),
+            }
+        }
+
+        for (path, error) in failures {
+            self.failed
+                .push((ReportEntry::new(path, source, None), error));
+        }
+
+        self.sort();
+    }
+
+    fn sort(&mut self) {
+        let key = |x: &ReportEntry| (x.source.clone(), x.location, x.path.clone());
+
+        self.applied.sort_by_key(key);
+        self.skipped.sort_by_key(|x| key(&x.0));
+        self.failed.sort_by_key(|x| key(&x.0));
+    }
+}
+
+impl Display for ConfigLoadReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} applied, {} skipped, {} failed.",
+            self.applied.len(),
+            self.skipped.len(),
+            self.failed.len()
+        )?;
+
+        for (entry, reason) in self.skipped.iter() {
+            write!(f, "\n{entry}: {reason}")?;
+        }
+
+        for (entry, error) in self.failed.iter() {
+            write!(f, "\n{entry}: {error}")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use bevy_app::{App, PreUpdate};
+use bevy_asset::{AssetApp, AssetServer, Assets, Handle, LoadState};
+use bevy_ecs::{
+    event::Event,
+    resource::Resource,
+    schedule::{IntoScheduleConfigs, SystemSet},
+    world::World,
+};
+use bevy_log::warn;
+use bevy_state::{
+    app::{AppExtStates, StatesPlugin},
+    condition::in_state,
+    state::{NextState, States},
+};
+
+use crate::builtin::ConfigLayers;
+
+use super::{CVarConfig, ConfigAssetLoader, ConfigLoader, DocumentContext};
+
+/// The state of the config layers loaded at startup by the [CVarLoaderPlugin](super::CVarLoaderPlugin).
+///
+/// Only present when the plugin is configured to load asset layers. Systems that depend on configuration should be gated
+/// behind [CVarLoadState::Ready], i.e. with `OnEnter(CVarLoadState::Ready)` or `run_if(in_state(CVarLoadState::Ready))`.
+///
+/// # Remarks
+/// `Startup` systems run before any asset can finish loading, so systems that need the final configuration
+/// should be moved to `OnEnter(CVarLoadState::Ready)` instead.
+#[derive(States, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CVarLoadState {
+    /// The asset layers are still loading, and the config hasn't been fully applied.
+    #[default]
+    Loading,
+    /// Every layer has been applied.
+    Ready,
+}
+
+/// The system set that loads and applies config assets, running in `PreUpdate`.
+/// Order your systems after it to see the results of a layer being applied on the same frame.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CVarLoadSet;
+
+/// Event sent when a config layer fails to load or apply during startup. The remaining layers are still applied.
+#[derive(Event, Debug, Clone)]
+pub struct ConfigLayerLoadFailed {
+    /// The asset path (or file path, for the user's config) of the layer.
+    pub path: String,
+    /// A description of what went wrong.
+    pub error: String,
+}
+
+/// The asset layers being loaded, and the user config (along with its includes) to apply on top of them once they're done.
+#[derive(Resource)]
+struct ConfigLayerAssets {
+    handles: Vec<(String, Handle<CVarConfig>)>,
+    loader: ConfigLoader,
+    user_config: Vec<DocumentContext<String>>,
+    user_loader: ConfigLoader,
+}
+
+/// Joins a layer's path onto the layer directory.
+fn layer_path(root: &str, layer: &str) -> String {
+    if root.is_empty() {
+        layer.to_owned()
+    } else {
+        format!("{}/{layer}", root.trim_end_matches('/'))
+    }
+}
+
+/// Returns whether the app can load asset layers, which requires the `AssetPlugin` and `StatesPlugin`.
+pub(crate) fn can_load_layers(app: &App) -> bool {
+    app.world().contains_resource::<AssetServer>() && app.is_plugin_added::<StatesPlugin>()
+}
+
+/// Starts loading the layers named by [ConfigLayers], applying them once they're all loaded.
+pub(crate) fn begin_loading_layers(
+    app: &mut App,
+    root: &str,
+    loader: ConfigLoader,
+    user_config: Vec<DocumentContext<String>>,
+    user_loader: ConfigLoader,
+) {
+    if !app.world().contains_resource::<Assets<CVarConfig>>() {
+        app.init_asset::<CVarConfig>()
+            .init_asset_loader::<ConfigAssetLoader>();
+
+        #[cfg(feature = "format_json")]
+        app.init_asset_loader::<ConfigAssetLoader<crate::format::JsonFormat>>();
+        #[cfg(feature = "format_ron")]
+        app.init_asset_loader::<ConfigAssetLoader<crate::format::RonFormat>>();
+    }
+
+    let asset_server = app.world().resource::<AssetServer>();
+
+    let handles = app
+        .world()
+        .resource::<ConfigLayers>()
+        .iter()
+        .map(|layer| {
+            let path = layer_path(root, layer);
+            let handle = asset_server.load(path.clone());
+
+            (path, handle)
+        })
+        .collect();
+
+    app.insert_resource(ConfigLayerAssets {
+        handles,
+        loader,
+        user_config,
+        user_loader,
+    });
+    app.init_state::<CVarLoadState>();
+    app.add_event::<ConfigLayerLoadFailed>();
+    app.add_systems(
+        PreUpdate,
+        apply_config_layers
+            .run_if(in_state(CVarLoadState::Loading))
+            .in_set(CVarLoadSet),
+    );
+}
+
+/// Waits for every asset layer to finish loading, then applies them in order followed by the user config.
+fn apply_config_layers(world: &mut World) {
+    let layers = world.resource::<ConfigLayerAssets>();
+    let asset_server = world.resource::<AssetServer>();
+
+    let done = layers.handles.iter().all(|(_, handle)| {
+        matches!(
+            asset_server.load_state(handle),
+            LoadState::Loaded | LoadState::Failed(_)
+        )
+    });
+
+    if !done {
+        return;
+    }
+
+    let (loader, user_loader) = (layers.loader, layers.user_loader);
+    let handles = layers.handles.clone();
+
+    for (path, handle) in handles {
+        let error = match world.resource::<AssetServer>().load_state(&handle) {
+            LoadState::Failed(e) => e.to_string(),
+            _ => match loader.apply_asset(world, handle) {
+                Ok(report) if report.is_ok() => continue,
+                Ok(report) => report.failures(),
+                Err(e) => e.to_string(),
+            },
+        };
+
+        warn!("Failed to load the config layer {path}, got error: {error}");
+        world.send_event(ConfigLayerLoadFailed { path, error });
+    }
+
+    let user_config = std::mem::take(&mut world.resource_mut::<ConfigLayerAssets>().user_config);
+
+    for user_config in user_config {
+        let path = user_config.source().to_owned();
+
+        let error = match user_loader.apply(world, user_config, true) {
+            Ok(report) if report.is_ok() => continue,
+            Ok(report) => report.failures(),
+            Err(e) => e.to_string(),
+        };
+
+        warn!("Failed to load the user's config file ({path}), got error: {error}");
+        world.send_event(ConfigLayerLoadFailed { path, error });
+    }
+
+    world
+        .resource_mut::<NextState<CVarLoadState>>()
+        .set(CVarLoadState::Ready);
+}
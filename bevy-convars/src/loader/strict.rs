@@ -0,0 +1,120 @@
+use std::fmt::Display;
+
+use bevy_log::warn;
+
+use crate::{CVarError, CVarManagement, layers::SourceLocation, reflect::ReflectCVar};
+
+use super::UnmatchedCVar;
+
+/// How a [ConfigLoader](super::ConfigLoader) treats keys that don't match any registered CVar, i.e. typos.
+///
+/// # Remarks
+/// Keys for CVars registered after the config is applied are unknown at the time, and as such are reported too.
+/// Those are still retained and applied once the CVar is registered (unless the layer is rejected).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum UnknownKeyPolicy {
+    /// Unknown keys are silently retained.
+    #[default]
+    Ignore,
+    /// Each unknown key is logged as a warning, along with its position and the closest CVar path.
+    Warn,
+    /// Any unknown key rejects the whole layer with [CVarError::UnknownConfigKeys], leaving the world unmodified.
+    Error,
+}
+
+/// A key in a config that doesn't match any registered CVar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownKey {
+    /// The full path of the key.
+    pub path: String,
+    /// The source of the config containing the key.
+    pub source: String,
+    /// Where the key was written within the source, if known.
+    pub location: Option<SourceLocation>,
+    /// The registered CVar path closest to the key, if any is close enough to likely be what was meant.
+    pub suggestion: Option<&'static str>,
+}
+
+impl Display for UnknownKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)?;
+
+        if let Some(location) = self.location {
+            write!(f, ":{location}")?;
+        }
+
+        write!(f, ": unknown key {}", self.path)?;
+
+        if let Some(suggestion) = self.suggestion {
+            write!(f, ", did you mean {suggestion}?")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the edit distance between two strings.
+fn edit_distance(left: &str, right: &str) -> usize {
+    let right: Vec<char> = right.chars().collect();
+    let mut previous: Vec<usize> = (0..=right.len()).collect();
+
+    for (i, l) in left.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, r) in right.iter().enumerate() {
+            let substitution = previous[j] + usize::from(l != *r);
+
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        previous = current;
+    }
+
+    previous[right.len()]
+}
+
+/// Returns the registered CVar path (or alias) closest to the given path, if it's close enough to likely be a typo of it.
+pub(crate) fn closest_cvar(management: &CVarManagement, path: &str) -> Option<&'static str> {
+    let max_distance = (path.chars().count() / 3).max(2);
+
+    management
+        .iterate_cvar_types()
+        .filter_map(|x| x.data::<ReflectCVar>())
+        .flat_map(|x| std::iter::once(x.cvar_path()).chain(x.aliases().iter().copied()))
+        .map(|x| (edit_distance(path, x), x))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|x| x.1)
+}
+
+/// Reports the keys of a config that didn't match any registered CVar according to the given policy, returning them.
+pub(crate) fn check_unknown_keys<'a>(
+    management: &CVarManagement,
+    policy: UnknownKeyPolicy,
+    source: &str,
+    unmatched: impl IntoIterator<Item = &'a UnmatchedCVar>,
+) -> Result<Vec<UnknownKey>, CVarError> {
+    let unknown: Vec<UnknownKey> = unmatched
+        .into_iter()
+        .map(|(path, _, location)| UnknownKey {
+            path: path.clone(),
+            source: source.to_owned(),
+            location: *location,
+            suggestion: closest_cvar(management, path),
+        })
+        .collect();
+
+    match policy {
+        UnknownKeyPolicy::Error if !unknown.is_empty() => {
+            return Err(CVarError::UnknownConfigKeys(unknown));
+        }
+        UnknownKeyPolicy::Warn => {
+            for key in unknown.iter() {
+                warn!("{key}");
+            }
+        }
+        _ => {}
+    }
+
+    Ok(unknown)
+}
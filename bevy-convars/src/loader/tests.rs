@@ -1,19 +1,29 @@
-use std::error::Error;
+use std::{collections::BTreeMap, error::Error};
 
+use bevy_app::App;
 use toml_edit::ImDocument;
 
 use crate::{
-    CVarManagement,
+    AppExtensions, CVarManagement, CVarsPlugin, WorldExtensions,
+    defaults::IsDefault,
+    layers::{ConfigLayer, LayerKind, SourceLocation},
+    parse::CVarOverride,
     reflect::CVarMeta,
-    tests::{TestArray, TestInteger, make_test_app},
+    storage::MemoryStorage,
+    tests::{
+        TestArray, TestCVarsPlugin, TestColor, TestColorValue, TestInteger, TestMap,
+        TestReflectOnly, TestReflectOnlyValue, TestStruct, TestStructList, TestStructValue,
+        TestVolume, make_test_app,
+    },
 };
 
 use super::{
-    ConfigLoader,
+    CVarLoaderPluginBuilder, ConfigLoader,
     cvar_doc::{CVarDocScanner, DocumentContext},
 };
 
 const TEST_DOCUMENT: &str = include_str!("test_document.toml");
+const TEST_TABLES_DOCUMENT: &str = include_str!("test_tables.toml");
 
 #[test]
 pub fn parse_test_document() {
@@ -24,11 +34,11 @@ pub fn parse_test_document() {
 
     let scanner = CVarDocScanner::new(document, false);
 
-    let cvars = scanner.find_cvars(app.world().resource::<CVarManagement>());
+    let cvars = scanner.scan(app.world()).cvars;
 
     println!("{:?}", cvars);
 
-    assert!(cvars.iter().any(|(key, _)| *key == TestArray::CVAR_PATH))
+    assert!(cvars.iter().any(|(key, _, _)| *key == TestArray::CVAR_PATH))
 }
 
 #[test]
@@ -50,3 +60,771 @@ pub fn apply_test_document() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[test]
+pub fn apply_standard_tables() -> Result<(), Box<dyn Error>> {
+    let mut app = make_test_app();
+
+    let loader = ConfigLoader::default();
+
+    let world = app.world_mut();
+
+    loader.apply_from_string(world, TEST_TABLES_DOCUMENT, Some("test_tables.toml"), false)?;
+
+    assert_eq!(
+        **world.resource::<TestStruct>(),
+        TestStructValue {
+            name: "standard".to_owned(),
+            weight: 7
+        }
+    );
+
+    assert_eq!(
+        world
+            .resource::<TestStructList>()
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>(),
+        ["first", "second"]
+    );
+
+    assert_eq!(
+        **world.resource::<TestMap>(),
+        BTreeMap::from([("alpha".to_owned(), 1), ("beta".to_owned(), 2)])
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn apply_non_serde_values() -> Result<(), Box<dyn Error>> {
+    let mut app = make_test_app();
+
+    let loader = ConfigLoader::default();
+
+    let world = app.world_mut();
+
+    loader.apply_from_string(
+        world,
+        "[testrig]\ntest_color = \"#00ff00\"\n\n[testrig.test_reflect_only]\nscale = 0.5\nlabel = \"small\"\n",
+        None,
+        false,
+    )?;
+
+    assert_eq!(**world.resource::<TestColor>(), TestColorValue(0x00ff00));
+    assert_eq!(
+        **world.resource::<TestReflectOnly>(),
+        TestReflectOnlyValue {
+            scale: 0.5,
+            label: "small".to_owned()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn apply_aliased_cvar() -> Result<(), Box<dyn Error>> {
+    let mut app = make_test_app();
+
+    let loader = ConfigLoader::default();
+
+    let world = app.world_mut();
+
+    loader.apply_from_string(world, "[testrig]\nold_volume = 0.75\n", None, false)?;
+
+    assert_eq!(**world.resource::<TestVolume>(), 0.75);
+
+    Ok(())
+}
+
+#[test]
+pub fn loader_plugin_before_registration() -> Result<(), Box<dyn Error>> {
+    let layer = DocumentContext::new(
+        ImDocument::parse(TEST_DOCUMENT.to_owned())?,
+        "test_document.toml".to_owned(),
+    );
+
+    let mut app = App::new();
+
+    app.add_plugins((
+        CVarLoaderPluginBuilder::default().add_layer(layer).build(),
+        CVarsPlugin,
+        TestCVarsPlugin,
+    ));
+
+    app.finish();
+
+    assert_eq!(**app.world().resource::<TestInteger>(), 4);
+
+    Ok(())
+}
+
+#[test]
+pub fn override_survives_user_config() -> Result<(), Box<dyn Error>> {
+    let overrides: Vec<CVarOverride> = vec!["testrig.test_int=12".parse()?];
+
+    let mut app = App::new();
+
+    app.add_plugins((
+        CVarLoaderPluginBuilder::default()
+            .with_user_config_storage(MemoryStorage::with_contents("[testrig]\ntest_int = 4\n"))
+            .build(),
+        CVarsPlugin,
+        TestCVarsPlugin,
+    ));
+
+    // Set before the app runs, so before the user's config is loaded.
+    app.world_mut().add_cvar_layer(ConfigLayer::from_overrides(
+        LayerKind::CommandLine,
+        "command line",
+        &overrides,
+    ));
+
+    app.finish();
+
+    let world = app.world();
+
+    assert_eq!(**world.resource::<TestInteger>(), 12);
+    assert_eq!(
+        world
+            .resource::<CVarManagement>()
+            .explain_cvar("testrig.test_int")?
+            .first()
+            .map(|x| x.kind),
+        Some(LayerKind::CommandLine)
+    );
+    // Overrides aren't saved to the user's config.
+    assert!(world.resource_ref::<TestInteger>().is_default());
+
+    Ok(())
+}
+
+#[test]
+pub fn apply_before_registration() -> Result<(), Box<dyn Error>> {
+    let mut app = App::new();
+
+    app.add_plugins(CVarsPlugin);
+
+    let loader = ConfigLoader::default();
+
+    loader.apply_from_string(
+        app.world_mut(),
+        TEST_TABLES_DOCUMENT,
+        Some("test_tables.toml"),
+        false,
+    )?;
+    loader.apply_from_string(
+        app.world_mut(),
+        "[testrig]\ntest_int = 9\nold_volume = 0.25\n",
+        Some("late.toml"),
+        false,
+    )?;
+
+    let management = app.world().resource::<CVarManagement>();
+    assert!(
+        management
+            .pending_cvars()
+            .any(|(path, pending)| path == TestInteger::CVAR_PATH && pending.source == "late.toml")
+    );
+
+    app.add_plugins(TestCVarsPlugin);
+    app.register_cvar::<TestVolume>();
+
+    let world = app.world();
+
+    assert_eq!(**world.resource::<TestInteger>(), 9);
+    assert_eq!(**world.resource::<TestVolume>(), 0.25);
+    assert_eq!(
+        **world.resource::<TestStruct>(),
+        TestStructValue {
+            name: "standard".to_owned(),
+            weight: 7
+        }
+    );
+    assert_eq!(
+        **world.resource::<TestMap>(),
+        BTreeMap::from([("alpha".to_owned(), 1), ("beta".to_owned(), 2)])
+    );
+    assert_eq!(world.resource::<TestStructList>().len(), 2);
+    assert_eq!(
+        world.resource::<CVarManagement>().pending_cvars().count(),
+        0
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "config_loader_asset")]
+pub fn hot_reload_config_asset() -> Result<(), Box<dyn Error>> {
+    use bevy_app::TaskPoolPlugin;
+    use bevy_asset::{AssetApp, AssetPlugin, Assets};
+
+    use super::{CVarConfig, CVarConfigHotReloadPlugin};
+    use crate::tests::TestBool;
+
+    let config = |document: &str| -> Result<CVarConfig, Box<dyn Error>> {
+        Ok(CVarConfig(vec![DocumentContext::new(
+            ImDocument::parse(document.to_owned())?,
+            "balance.toml".to_owned(),
+        )]))
+    };
+
+    let mut app = make_test_app();
+
+    app.add_plugins((
+        TaskPoolPlugin::default(),
+        AssetPlugin::default(),
+        CVarConfigHotReloadPlugin,
+    ));
+    app.init_asset::<CVarConfig>();
+
+    let loader = ConfigLoader::default();
+
+    loader.apply_from_string(
+        app.world_mut(),
+        "[testrig]\ntest_bool = false\n",
+        Some("base.toml"),
+        false,
+    )?;
+
+    let handle = app
+        .world_mut()
+        .resource_mut::<Assets<CVarConfig>>()
+        .add(config(
+            "[testrig]\ntest_bool = true\ntest_int = 7\ntest_array = [1]\n[modded]\nremoved = 1\nkept = 2\n",
+        )?);
+
+    loader.apply_asset(app.world_mut(), handle.clone())?;
+
+    assert!(**app.world().resource::<TestBool>());
+    assert_eq!(**app.world().resource::<TestInteger>(), 7);
+
+    // Unchanged keys in the asset shouldn't clobber runtime changes.
+    **app.world_mut().resource_mut::<TestArray>() = vec![2];
+
+    app.world_mut().resource_mut::<Assets<CVarConfig>>().insert(
+        &handle,
+        config("[testrig]\ntest_int = 8\ntest_array = [1]\n[modded]\nkept = 3\n")?,
+    );
+
+    app.update();
+    app.update();
+
+    let world = app.world();
+
+    assert_eq!(**world.resource::<TestInteger>(), 8);
+    assert!(!**world.resource::<TestBool>());
+    assert_eq!(**world.resource::<TestArray>(), [2]);
+
+    // Values waiting for their CVar only come from the current version of the asset.
+    let pending: Vec<(&str, String)> = world
+        .resource::<CVarManagement>()
+        .pending_cvars()
+        .map(|(path, x)| (path, x.value.to_string()))
+        .collect();
+
+    assert_eq!(pending, [("modded.kept", "3".to_owned())]);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "config_loader_asset")]
+pub fn load_asset_layers() -> Result<(), Box<dyn Error>> {
+    use bevy_app::TaskPoolPlugin;
+    use bevy_asset::AssetPlugin;
+    use bevy_ecs::event::Events;
+    use bevy_state::{app::StatesPlugin, state::State};
+
+    use super::{CVarLoadState, ConfigLayerLoadFailed};
+
+    let layers = DocumentContext::new(
+        ImDocument::parse(
+            "[core]\nconfig_layers = [\"test_document.toml\", \"missing.toml\", \"test_tables.toml\"]\n"
+                .to_owned(),
+        )?,
+        "layers.toml".to_owned(),
+    );
+
+    let mut app = App::new();
+
+    app.add_plugins((
+        TaskPoolPlugin::default(),
+        AssetPlugin {
+            file_path: "src/loader".to_owned(),
+            ..Default::default()
+        },
+        StatesPlugin,
+        CVarsPlugin,
+        TestCVarsPlugin,
+        CVarLoaderPluginBuilder::default()
+            .add_layer(layers)
+            .with_asset_layers("")
+            .build(),
+    ));
+
+    app.finish();
+    app.cleanup();
+
+    for _ in 0..100 {
+        app.update();
+
+        if *app.world().resource::<State<CVarLoadState>>().get() == CVarLoadState::Ready {
+            break;
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    let world = app.world();
+
+    assert_eq!(
+        *world.resource::<State<CVarLoadState>>().get(),
+        CVarLoadState::Ready
+    );
+    assert_eq!(**world.resource::<TestInteger>(), 4);
+    assert_eq!(
+        **world.resource::<TestStruct>(),
+        TestStructValue {
+            name: "standard".to_owned(),
+            weight: 7
+        }
+    );
+
+    let events = world.resource::<Events<ConfigLayerLoadFailed>>();
+    let failures: Vec<_> = events.iter_current_update_events().collect();
+
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].path, "missing.toml");
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "config_loader_asset")]
+pub fn fancy_without_asset_plugin() {
+    let mut app = App::new();
+
+    app.add_plugins((
+        CVarsPlugin,
+        TestCVarsPlugin,
+        CVarLoaderPluginBuilder::fancy()
+            .with_user_config_storage(MemoryStorage::with_contents("[testrig]\ntest_int = 4\n"))
+            .build(),
+    ));
+
+    // Asset layers are skipped, rather than panicking, and the user's config is applied right away.
+    app.finish();
+
+    assert_eq!(**app.world().resource::<TestInteger>(), 4);
+}
+
+#[test]
+#[cfg(feature = "config_loader_asset")]
+pub fn apply_unloaded_asset() {
+    use bevy_app::TaskPoolPlugin;
+    use bevy_asset::{AssetApp, AssetPlugin, Handle};
+
+    use super::CVarConfig;
+    use crate::CVarError;
+
+    let mut app = make_test_app();
+
+    app.add_plugins((TaskPoolPlugin::default(), AssetPlugin::default()));
+    app.init_asset::<CVarConfig>();
+
+    let res = ConfigLoader::default().apply_asset(app.world_mut(), Handle::default());
+
+    assert!(matches!(res, Err(CVarError::AssetNotLoaded)));
+}
+
+#[test]
+pub fn explain_layered_cvar() -> Result<(), Box<dyn Error>> {
+    let mut app = make_test_app();
+
+    let loader = ConfigLoader::default();
+
+    loader.apply_from_string(
+        app.world_mut(),
+        "# Embedded defaults.\n[testrig]\ntest_int = 2\n",
+        Some("embedded.toml"),
+        false,
+    )?;
+    loader.apply_from_string(
+        app.world_mut(),
+        "\n[testrig]\n  test_int = 5\n  old_volume = 0.5\n",
+        Some("user.toml"),
+        true,
+    )?;
+
+    let management = app.world().resource::<CVarManagement>();
+    let entries = management.explain_cvar(TestInteger::CVAR_PATH)?;
+
+    assert_eq!(
+        entries
+            .iter()
+            .map(|x| (x.source.as_str(), x.effective, x.value.as_integer()))
+            .collect::<Vec<_>>(),
+        [
+            ("user.toml", true, Some(5)),
+            ("embedded.toml", false, Some(2))
+        ]
+    );
+    assert_eq!(
+        entries[0].location,
+        Some(SourceLocation { line: 3, column: 3 })
+    );
+    assert_eq!(
+        entries[1].location,
+        Some(SourceLocation { line: 3, column: 1 })
+    );
+    assert_eq!(
+        entries[0].to_string(),
+        "user.toml:3:3 (User) = 5 (effective)"
+    );
+
+    // Aliases resolve to the CVar they belong to.
+    let entries = management.explain_cvar("testrig.old_volume")?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].location,
+        Some(SourceLocation { line: 4, column: 3 })
+    );
+
+    // No entries means the default value.
+    assert!(management.explain_cvar("testrig.test_bool")?.is_empty());
+    assert!(management.explain_cvar("testrig.missing").is_err());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "config_loader_fs")]
+pub fn load_file_includes() -> Result<(), Box<dyn Error>> {
+    use super::ConfigLoaderError;
+    use crate::tests::TestBool;
+
+    let mut app = make_test_app();
+
+    let loader = ConfigLoader::default();
+    let documents = loader.load_file("src/loader/test_includes/main.toml")?;
+
+    // Included configs come first, each as its own document.
+    let sources: Vec<_> = documents
+        .iter()
+        .map(|x| x.source().replace('\\', "/"))
+        .collect();
+
+    assert_eq!(
+        sources,
+        [
+            "src/loader/test_includes/shared.toml",
+            "src/loader/test_includes/graphics/high.toml",
+            "src/loader/test_includes/main.toml"
+        ]
+    );
+    assert_eq!(documents[2].included_from(), None);
+    assert_eq!(
+        documents[0].included_from().map(|x| x.replace('\\', "/")),
+        Some("src/loader/test_includes/graphics/high.toml".to_owned())
+    );
+
+    for document in documents {
+        loader.apply(app.world_mut(), document, false)?;
+    }
+
+    let world = app.world();
+
+    assert_eq!(**world.resource::<TestInteger>(), 3);
+    assert!(!**world.resource::<TestBool>());
+    assert_eq!(**world.resource::<TestArray>(), [4, 5]);
+    assert_eq!(
+        world
+            .resource::<CVarManagement>()
+            .explain_cvar(TestInteger::CVAR_PATH)?
+            .len(),
+        3
+    );
+
+    assert!(matches!(
+        loader.load_file("src/loader/test_includes/cycle_a.toml"),
+        Err(ConfigLoaderError::IncludeCycle(_))
+    ));
+    assert!(matches!(
+        loader.load_file("src/loader/test_includes/broken.toml"),
+        Err(ConfigLoaderError::Include { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "config_loader_asset")]
+pub fn load_asset_includes() -> Result<(), Box<dyn Error>> {
+    use bevy_app::TaskPoolPlugin;
+    use bevy_asset::{AssetApp, AssetPlugin, AssetServer, Handle, LoadState};
+
+    use super::{CVarConfig, ConfigAssetLoader};
+    use crate::tests::TestBool;
+
+    let mut app = make_test_app();
+
+    app.add_plugins((
+        TaskPoolPlugin::default(),
+        AssetPlugin {
+            file_path: "src/loader/test_includes".to_owned(),
+            ..Default::default()
+        },
+    ));
+    app.init_asset::<CVarConfig>()
+        .init_asset_loader::<ConfigAssetLoader>();
+
+    let asset_server = app.world().resource::<AssetServer>();
+    let main: Handle<CVarConfig> = asset_server.load("main.toml");
+    let cycle: Handle<CVarConfig> = asset_server.load("cycle_a.toml");
+
+    let loaded = |app: &App, handle: &Handle<CVarConfig>| {
+        matches!(
+            app.world().resource::<AssetServer>().load_state(handle),
+            LoadState::Loaded | LoadState::Failed(_)
+        )
+    };
+
+    for _ in 0..100 {
+        app.update();
+
+        if loaded(&app, &main) && loaded(&app, &cycle) {
+            break;
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    let asset_server = app.world().resource::<AssetServer>();
+
+    assert!(matches!(
+        asset_server.load_state(&cycle),
+        LoadState::Failed(_)
+    ));
+
+    let sources: Vec<_> = app
+        .world()
+        .resource::<bevy_asset::Assets<CVarConfig>>()
+        .get(&main)
+        .unwrap()
+        .documents()
+        .iter()
+        .map(|x| x.source().to_owned())
+        .collect();
+
+    assert_eq!(sources, ["shared.toml", "graphics/high.toml", "main.toml"]);
+
+    ConfigLoader::default().apply_asset(app.world_mut(), main)?;
+
+    let world = app.world();
+
+    assert_eq!(**world.resource::<TestInteger>(), 3);
+    assert!(!**world.resource::<TestBool>());
+    assert_eq!(**world.resource::<TestArray>(), [4, 5]);
+
+    Ok(())
+}
+
+#[test]
+pub fn apply_conditional_sections() -> Result<(), Box<dyn Error>> {
+    use super::ConfigFeatures;
+    use crate::tests::TestBool;
+
+    let mut app = make_test_app();
+
+    app.insert_resource(ConfigFeatures(["steam".to_owned()].into_iter().collect()));
+
+    ConfigLoader::default().apply_from_string(
+        app.world_mut(),
+        r#"
+[testrig]
+test_int = 1
+
+[when.debug_assertions]
+testrig.test_int = 2
+
+[when."!debug_assertions"]
+testrig.test_int = 3
+
+[when."feature == 'steam'"]
+testrig.test_array = [9]
+
+[when."testrig.test_bool == true"]
+testrig.test_array = [10]
+
+[when."testrig.test_bool != true"]
+testrig.test_array = [11]
+
+[when."target_os == 'plan9'"]
+testrig.test_bool = false
+
+[when."testrig.missing"]
+testrig.test_bool = false
+"#,
+        Some("conditional.toml"),
+        false,
+    )?;
+
+    let world = app.world();
+
+    assert_eq!(
+        **world.resource::<TestInteger>(),
+        if cfg!(debug_assertions) { 2 } else { 3 }
+    );
+    // Later sections that hold override earlier ones.
+    assert_eq!(**world.resource::<TestArray>(), [10]);
+    assert!(**world.resource::<TestBool>());
+
+    // Values are compared as the CVar's type, so an integer matches a float CVar.
+    app.world_mut()
+        .set_cvar_from_str("testrig.derived.volume", "1.0")?;
+
+    ConfigLoader::default().apply_from_string(
+        app.world_mut(),
+        "[when.\"testrig.derived.volume == 1\"]\ntestrig.test_int = 5\n",
+        Some("typed.toml"),
+        false,
+    )?;
+
+    assert_eq!(**app.world().resource::<TestInteger>(), 5);
+
+    Ok(())
+}
+
+#[test]
+pub fn report_unknown_keys() -> Result<(), Box<dyn Error>> {
+    use super::{UnknownKey, UnknownKeyPolicy};
+    use crate::CVarError;
+
+    let mut app = make_test_app();
+
+    let document = "[testrig]\ntest_int = 4\ntest_itn = 5\n\n[render]\nfoo = 1\n";
+    let default = **app.world().resource::<TestInteger>();
+
+    let res = ConfigLoader::default()
+        .with_unknown_keys(UnknownKeyPolicy::Error)
+        .apply_from_string(app.world_mut(), document, Some("typo.toml"), false);
+
+    let Err(CVarError::UnknownConfigKeys(keys)) = res else {
+        panic!("Expected the layer to be rejected, got {res:?}");
+    };
+
+    assert_eq!(
+        keys,
+        [
+            UnknownKey {
+                path: "testrig.test_itn".to_owned(),
+                source: "typo.toml".to_owned(),
+                location: Some(SourceLocation { line: 3, column: 1 }),
+                suggestion: Some("testrig.test_int"),
+            },
+            UnknownKey {
+                path: "render.foo".to_owned(),
+                source: "typo.toml".to_owned(),
+                location: Some(SourceLocation { line: 6, column: 1 }),
+                suggestion: None,
+            }
+        ]
+    );
+    assert_eq!(
+        keys[0].to_string(),
+        "typo.toml:3:1: unknown key testrig.test_itn, did you mean testrig.test_int?"
+    );
+
+    // Rejected layers leave the world untouched.
+    assert_eq!(**app.world().resource::<TestInteger>(), default);
+    assert!(
+        app.world()
+            .resource::<CVarManagement>()
+            .pending_cvars()
+            .next()
+            .is_none()
+    );
+
+    ConfigLoader::default()
+        .with_unknown_keys(UnknownKeyPolicy::Warn)
+        .apply_from_string(app.world_mut(), document, Some("typo.toml"), false)?;
+
+    assert_eq!(**app.world().resource::<TestInteger>(), 4);
+
+    // Unknown keys in the user config's conditional sections aren't kept as orphans, but are still reported.
+    let res = ConfigLoader::default()
+        .with_unknown_keys(UnknownKeyPolicy::Error)
+        .apply_from_string(
+            app.world_mut(),
+            "[when.\"testrig.test_bool == true\"]\ntestrig.test_itn = 5\n",
+            Some("user.toml"),
+            true,
+        );
+
+    let Err(CVarError::UnknownConfigKeys(keys)) = res else {
+        panic!("Expected the layer to be rejected, got {res:?}");
+    };
+
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0].path, "testrig.test_itn");
+    assert_eq!(
+        keys[0].location,
+        Some(SourceLocation { line: 2, column: 9 })
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn report_every_key() -> Result<(), Box<dyn Error>> {
+    use super::{ReportEntry, SkipReason};
+    use crate::CVarError;
+
+    let mut app = make_test_app();
+
+    let document = "[testrig]\ntest_int = 4\ntest_itn = 5\nold_volume = 2.0\n\n[when.\"testrig.test_bool == false\"]\ntestrig.test_array = [1]\n";
+    let entry = |path: &str, line, column| ReportEntry {
+        path: path.to_owned(),
+        source: "report.toml".to_owned(),
+        location: Some(SourceLocation { line, column }),
+    };
+
+    let report = ConfigLoader::default().apply_from_string(
+        app.world_mut(),
+        document,
+        Some("report.toml"),
+        false,
+    )?;
+
+    assert_eq!(report.applied, [entry("testrig.test_int", 2, 1)]);
+    assert_eq!(
+        report.skipped,
+        [
+            (
+                entry("testrig.test_itn", 3, 1),
+                SkipReason::UnknownKey {
+                    suggestion: Some("testrig.test_int")
+                }
+            ),
+            (
+                entry("testrig.test_array", 7, 9),
+                SkipReason::ConditionNotMet("testrig.test_bool is true".to_owned())
+            ),
+        ]
+    );
+
+    // Keys written with an alias are reported under the CVar's path.
+    assert!(!report.is_ok());
+    assert!(matches!(
+        report.failed.as_slice(),
+        [(failed, CVarError::ConstraintViolation(_))] if *failed == entry("testrig.derived.volume", 4, 1)
+    ));
+
+    // The remaining keys are still applied.
+    assert_eq!(**app.world().resource::<TestInteger>(), 4);
+
+    Ok(())
+}
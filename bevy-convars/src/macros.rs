@@ -5,18 +5,16 @@ macro_rules! cvar {
     ($(#[$cvar_doc:meta])*
         $cvar_ident:ident($cvar_path:literal, $cvar_flags:expr): $cvar_ty:ty = $cvar_default:expr
     ) => {
-        #[allow(unused_imports, reason = "Working around limitations of rust and bevy's macros.")]
-        use $crate::reexports::jank::*;
-
         #[derive(
             $crate::reexports::bevy_reflect::Reflect,
         )]
-        #[reflect(Default__MACRO_JANK, Resource__MACRO_JANK, CVar__MACRO_JANK)]
         $(
             #[$cvar_doc]
         )*
         pub struct $cvar_ident($cvar_ty);
 
+        const _: () = $crate::path::assert_valid_cvar_path($cvar_path);
+
         impl  $crate::reexports::bevy_ecs::prelude::Resource for $cvar_ident { }
 
         impl ::std::ops::Deref for $cvar_ident {
@@ -43,7 +41,7 @@ macro_rules! cvar {
             type Inner = $cvar_ty;
             const CVAR_PATH: &'static str = $cvar_path;
 
-            fn flags() -> CVarFlags {
+            fn flags() -> $crate::CVarFlags {
                 $cvar_flags
             }
 
@@ -54,6 +52,15 @@ macro_rules! cvar {
             fn set_to_default(&mut self) {
                 self.0 = Self::default_inner();
             }
+
+            fn register_value_type_data(registry: &mut $crate::reexports::bevy_reflect::TypeRegistry) {
+                #[allow(unused_imports)]
+                use $crate::reflect::{RegisterSerde as _, RegisterSerdeFallback as _, RegisterString as _, RegisterStringFallback as _};
+
+                // Serde and string conversion support are both optional, and registered only if the value type implements them.
+                (&$crate::reflect::TypeDataProbe::<$cvar_ty>::new()).register_serde(registry);
+                (&$crate::reflect::TypeDataProbe::<$cvar_ty>::new()).register_string(registry);
+            }
         }
     };
 }
@@ -112,55 +119,23 @@ macro_rules! cvar_collection {
             $crate::cvar!($(#[$cvar_doc])* $cvar_ident($cvar_path, $cvar_flags): $cvar_ty = $cvar_default);
         )*
 
+        const _: () = $crate::path::assert_valid_cvar_paths(&[$($cvar_path),*]);
+
         $(#[$plugin_doc])*
         #[derive(::std::default::Default)]
         $plugin_vis struct $cvar_collection_plugin;
 
+        impl $cvar_collection_plugin {
+            /// The paths of every CVar in this collection, see [assert_no_path_overlaps]($crate::path::assert_no_path_overlaps).
+            #[allow(dead_code)]
+            pub const CVAR_PATHS: &'static [&'static str] = &[$($cvar_path),*];
+        }
+
         impl $crate::reexports::bevy_app::prelude::Plugin for $cvar_collection_plugin {
             fn build(&self, app: &mut $crate::reexports::bevy_app::prelude::App) {
-                let mut management = app.world_mut().remove_resource::<$crate::CVarManagement>().unwrap();
                 $(
-                    app.register_type::<$cvar_ident>();
-                    app.register_type::<$cvar_ty>();
-
-
-                    app.insert_resource::<$cvar_ident>($cvar_ident::default());
-                    management.register_cvar::<$cvar_ident>(app);
-                    // Yes, these always run. I doubt it matters, but they do.
-                    app.add_systems($crate::reexports::bevy_app::prelude::Last,
-                        $crate::cvar_modified_system::<$cvar_ident>
-                    );
-
-                    {
-                        let mut type_registry = app.world().resource::<$crate::reexports::bevy_ecs::prelude::AppTypeRegistry>().write();
-
-                        if ($cvar_flags).contains($crate::CVarFlags::SAVED) {
-                            type_registry.register_type_data::<$cvar_ty, $crate::reexports::bevy_reflect::ReflectDeserialize>();
-                            type_registry.register_type_data::<$cvar_ty, $crate::reexports::bevy_reflect::ReflectSerialize>();
-
-                            // TODO(kaylie): This REQUIRES that a cvar's type be serializable, which may be undesired. Figure out a nice way to macro pattern match a solution.
-                            /*
-                            ::std::assert!(
-                                type_registry.get_type_data::<$crate::reexports::bevy_reflect::ReflectDeserialize>(::std::any::TypeId::of::<$cvar_ty>()).is_some(),
-                                "CVar {} was registered as being a SAVED or MIRRORED cvar, but its value lacks reflection deserialization.",
-                                stringify!($cvar_ident)
-                            );
-
-                            ::std::assert!(
-                                type_registry.get_type_data::<$crate::reexports::bevy_reflect::ReflectSerialize>(::std::any::TypeId::of::<$cvar_ty>()).is_some(),
-                                "CVar {} was registered as being a SAVED or MIRRORED cvar, but its value lacks reflection serialization.",
-                                stringify!($cvar_ident)
-                            );
-                            */
-                        }
-                    }
+                    $crate::AppExtensions::register_cvar::<$cvar_ident>(app);
                 )*
-
-                app.world_mut().insert_resource(management);
-
-                // Ensure our default values stay marked as default by incrementing the change tick.
-                // Due to how change ticks work there's zero consequences.
-                app.world_mut().increment_change_tick();
             }
         }
     };
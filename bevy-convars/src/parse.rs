@@ -46,6 +46,8 @@ impl TryFrom<&str> for CVarOverride {
             .split_once('=')
             .ok_or(CVarOverrideParseError::DoesntLookLikeAnOverride)?;
 
+        crate::path::validate_cvar_path(left).map_err(|_| CVarOverrideParseError::InvalidPath)?;
+
         let value =
             toml_edit::Value::from_str(right).map_err(|_| CVarOverrideParseError::InvalidToml)?;
 
@@ -0,0 +1,221 @@
+//! Provides validation of CVar paths, usable in const contexts so that mistakes fail the build.
+//!
+//! The macros validate every path they're given for you, and check that paths within a collection don't overlap.
+//! Overlaps between collections can only be detected once they're registered, see [assert_no_path_overlaps] for catching them in a test.
+//!
+//! # Example
+//! ```compile_fail
+//! # use bevy_convars::*;
+//! cvar_collection! {
+//!     pub struct BadCVars & BadCVarsMut {
+//!         // Fails to compile, as the path contains an empty segment.
+//!         bad = cvar Bad("render..aa", CVarFlags::LOCAL): bool = false
+//!     }
+//!
+//!     pub struct BadCVarsPlugin;
+//! }
+//! ```
+//!
+//! ```compile_fail
+//! # use bevy_convars::*;
+//! cvar_collection! {
+//!     pub struct BadCVars & BadCVarsMut {
+//!         // Fails to compile, as `render.aa` can't be both a value and a table.
+//!         aa = cvar Aa("render.aa", CVarFlags::LOCAL): bool = false,
+//!         aa_method = cvar AaMethod("render.aa.method", CVarFlags::LOCAL): u32 = 0,
+//!     }
+//!
+//!     pub struct BadCVarsPlugin;
+//! }
+//! ```
+
+use std::fmt::Display;
+
+#[cfg(test)]
+mod tests;
+
+/// Errors that can occur when validating a CVar path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CVarPathError {
+    /// Error indicating the path is empty.
+    Empty,
+    /// Error indicating the path has an empty segment, i.e. leading, trailing or repeated dots.
+    EmptySegment,
+    /// Error indicating the path contains a character other than ASCII letters, digits, `_`, `-` and the `.` separator.
+    InvalidCharacter,
+    /// Error indicating the path is the same as another path.
+    Duplicate,
+    /// Error indicating the path is a prefix of another path, and as such would have to be both a value and a table.
+    Overlap,
+}
+
+impl CVarPathError {
+    /// Returns a description of the error, usable in const contexts.
+    pub const fn message(&self) -> &'static str {
+        match self {
+            CVarPathError::Empty => "CVar paths must not be empty.",
+            CVarPathError::EmptySegment => {
+                "CVar paths must not have empty segments (leading, trailing or repeated dots)."
+            }
+            CVarPathError::InvalidCharacter => {
+                "CVar paths may only contain ASCII letters, digits, `_` and `-`, separated by `.`."
+            }
+            CVarPathError::Duplicate => "CVar paths must be unique.",
+            CVarPathError::Overlap => {
+                "A CVar path must not be a prefix of another, a CVar cannot be both a value and a table."
+            }
+        }
+    }
+}
+
+impl Display for CVarPathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for CVarPathError {}
+
+/// Validates the syntax of a CVar path.
+pub const fn validate_cvar_path(path: &str) -> Result<(), CVarPathError> {
+    let bytes = path.as_bytes();
+
+    if bytes.is_empty() {
+        return Err(CVarPathError::Empty);
+    }
+
+    let mut idx = 0;
+    let mut segment_len = 0;
+
+    while idx < bytes.len() {
+        let c = bytes[idx];
+
+        if c == b'.' {
+            if segment_len == 0 {
+                return Err(CVarPathError::EmptySegment);
+            }
+
+            segment_len = 0;
+        } else if c.is_ascii_alphanumeric() || c == b'_' || c == b'-' {
+            segment_len += 1;
+        } else {
+            return Err(CVarPathError::InvalidCharacter);
+        }
+
+        idx += 1;
+    }
+
+    if segment_len == 0 {
+        return Err(CVarPathError::EmptySegment);
+    }
+
+    Ok(())
+}
+
+/// Checks whether two paths conflict, i.e. are equal or one is a table containing the other.
+pub const fn check_path_overlap(left: &str, right: &str) -> Result<(), CVarPathError> {
+    let (left, right) = (left.as_bytes(), right.as_bytes());
+
+    let (shorter, longer) = if left.len() <= right.len() {
+        (left, right)
+    } else {
+        (right, left)
+    };
+
+    let mut idx = 0;
+
+    while idx < shorter.len() {
+        if shorter[idx] != longer[idx] {
+            return Ok(());
+        }
+
+        idx += 1;
+    }
+
+    if shorter.len() == longer.len() {
+        Err(CVarPathError::Duplicate)
+    } else if longer[shorter.len()] == b'.' {
+        Err(CVarPathError::Overlap)
+    } else {
+        Ok(())
+    }
+}
+
+/// Panics if the given path is invalid. Used by the macros to fail the build on invalid paths.
+pub const fn assert_valid_cvar_path(path: &str) {
+    if let Err(e) = validate_cvar_path(path) {
+        panic!("{}", e.message());
+    }
+}
+
+/// Panics if any of the given paths are invalid or conflict with each other. Used by the macros to fail the build on invalid collections.
+pub const fn assert_valid_cvar_paths(paths: &[&str]) {
+    let mut left = 0;
+
+    while left < paths.len() {
+        assert_valid_cvar_path(paths[left]);
+
+        let mut right = left + 1;
+
+        while right < paths.len() {
+            if let Err(e) = check_path_overlap(paths[left], paths[right]) {
+                panic!("{}", e.message());
+            }
+
+            right += 1;
+        }
+
+        left += 1;
+    }
+}
+
+/// Finds the first pair of conflicting paths between the given collections, if any.
+/// # Remarks
+/// Paths within the same collection aren't compared, as the macros already check those.
+pub fn find_path_overlap<'a>(
+    collections: &[&[&'a str]],
+) -> Option<(&'a str, &'a str, CVarPathError)> {
+    for (idx, left) in collections.iter().enumerate() {
+        for right in &collections[idx + 1..] {
+            for left_path in left.iter() {
+                for right_path in right.iter() {
+                    if let Err(e) = check_path_overlap(left_path, right_path) {
+                        return Some((*left_path, *right_path, e));
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Panics if any paths conflict between the given collections. Intended for use in your tests, to catch overlaps before they panic at registration.
+///
+/// # Example
+/// ```
+/// # use bevy_convars::{*, path::assert_no_path_overlaps};
+/// cvar_collection! {
+///     pub struct RenderCVars & RenderCVarsMut {
+///         enable_xr = cvar EnableXr("render.enable_xr", CVarFlags::SAVED): bool = false,
+///     }
+///
+///     pub struct RenderCVarsPlugin;
+/// }
+///
+/// cvar_collection! {
+///     pub struct AudioCVars & AudioCVarsMut {
+///         volume = cvar Volume("audio.volume", CVarFlags::SAVED): f32 = 1.0,
+///     }
+///
+///     pub struct AudioCVarsPlugin;
+/// }
+///
+/// assert_no_path_overlaps(&[RenderCVarsPlugin::CVAR_PATHS, AudioCVarsPlugin::CVAR_PATHS]);
+/// ```
+pub fn assert_no_path_overlaps(collections: &[&[&str]]) {
+    if let Some((left, right, e)) = find_path_overlap(collections) {
+        panic!("CVar paths {left} and {right} conflict: {e}");
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{builtin::CoreCVarsPlugin, tests::TestCVarsPlugin};
+
+use super::{
+    CVarPathError, assert_no_path_overlaps, check_path_overlap, find_path_overlap,
+    validate_cvar_path,
+};
+
+#[test]
+pub fn validate_paths() {
+    assert_eq!(validate_cvar_path("render.aa.method"), Ok(()));
+    assert_eq!(validate_cvar_path("render.msaa_samples-2"), Ok(()));
+    assert_eq!(validate_cvar_path("core"), Ok(()));
+
+    assert_eq!(validate_cvar_path(""), Err(CVarPathError::Empty));
+    assert_eq!(
+        validate_cvar_path("render..aa"),
+        Err(CVarPathError::EmptySegment)
+    );
+    assert_eq!(
+        validate_cvar_path("render.aa."),
+        Err(CVarPathError::EmptySegment)
+    );
+    assert_eq!(
+        validate_cvar_path(".render"),
+        Err(CVarPathError::EmptySegment)
+    );
+    assert_eq!(
+        validate_cvar_path("render.aa method"),
+        Err(CVarPathError::InvalidCharacter)
+    );
+    assert_eq!(
+        validate_cvar_path("render.\"aa\""),
+        Err(CVarPathError::InvalidCharacter)
+    );
+}
+
+#[test]
+pub fn check_overlaps() {
+    assert_eq!(check_path_overlap("render.aa", "render.ab"), Ok(()));
+    assert_eq!(check_path_overlap("render.aa", "render.aa_method"), Ok(()));
+    assert_eq!(
+        check_path_overlap("render.aa", "render.aa"),
+        Err(CVarPathError::Duplicate)
+    );
+    assert_eq!(
+        check_path_overlap("render.aa.method", "render.aa"),
+        Err(CVarPathError::Overlap)
+    );
+}
+
+#[test]
+pub fn no_overlaps_between_collections() {
+    assert_no_path_overlaps(&[CoreCVarsPlugin::CVAR_PATHS, TestCVarsPlugin::CVAR_PATHS]);
+
+    assert_eq!(
+        find_path_overlap(&[
+            TestCVarsPlugin::CVAR_PATHS,
+            &["render.aa"],
+            &["testrig.test_int.shadowed"]
+        ]),
+        Some((
+            "testrig.test_int",
+            "testrig.test_int.shadowed",
+            CVarPathError::Overlap
+        ))
+    );
+}
+
+#[test]
+#[should_panic(expected = "CVar paths testrig.test_bool and testrig.test_bool conflict")]
+pub fn overlap_between_collections() {
+    assert_no_path_overlaps(&[TestCVarsPlugin::CVAR_PATHS, &["testrig.test_bool"]]);
+}
@@ -10,10 +10,14 @@ pub use crate::loader::CVarLoaderPluginBuilder;
 #[cfg(feature = "config_loader")]
 pub use crate::loader::CVarLoaderPlugin;
 
+#[cfg(feature = "config_loader_asset")]
+pub use crate::loader::{CVarLoadSet, CVarLoadState};
+
 #[cfg(feature = "parse_cvars")]
 pub use crate::parse::CVarOverride;
 
 #[cfg(feature = "parse_cvars")]
 pub use crate::save::CVarSaveContext;
 
+pub use crate::AppExtensions;
 pub use crate::WorldExtensions;
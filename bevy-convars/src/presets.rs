@@ -0,0 +1,172 @@
+//! Provides named presets, partial configs that can be applied as a batch (i.e. `graphics.low` through `graphics.ultra`).
+//!
+//! Presets are registered with [AppExtensions::register_cvar_preset](crate::AppExtensions::register_cvar_preset), and applied with
+//! [WorldExtensions::apply_cvar_preset](crate::WorldExtensions::apply_cvar_preset). Presets sharing a group (the part of their name
+//! before the last `.`) are alternatives to each other, and [CVarPresets::active] tells which of them the current values still match.
+//!
+//! # Example
+//! ```
+//! # use bevy_app::prelude::*;
+//! # use bevy_convars::prelude::*;
+//! # use bevy_convars::presets::{CVarPreset, CVarPresets};
+//! # bevy_convars::cvar_collection! {
+//! #     pub struct RenderCVars & RenderCVarsMut {
+//! #         enable_ssao = cvar EnableSsao("render.ssao.enabled", CVarFlags::SAVED): bool = true,
+//! #     }
+//! #     pub struct RenderCVarsPlugin;
+//! # }
+//! let mut app = App::new();
+//! app.add_plugins((CVarsPlugin, RenderCVarsPlugin));
+//!
+//! app.register_cvar_preset(
+//!     CVarPreset::new("graphics.low", "For older hardware.").with_value("render.ssao.enabled", false),
+//! );
+//! app.register_cvar_preset(
+//!     CVarPreset::new("graphics.high", "For most hardware.").with_value("render.ssao.enabled", true),
+//! );
+//!
+//! let world = app.world_mut();
+//! world.apply_cvar_preset("graphics.low").unwrap();
+//!
+//! // Shown as "Custom" by a settings menu if the user diverges from every preset.
+//! let active = world.resource::<CVarPresets>().active(world, "graphics").map(|x| x.name());
+//! assert_eq!(active, Some("graphics.low"));
+//! ```
+
+use bevy_ecs::{reflect::AppTypeRegistry, resource::Resource, world::World};
+use serde::de::IntoDeserializer;
+use toml_edit::Value;
+
+use crate::{CVarError, CVarManagement, layers::same_value, save::serialize_value};
+
+#[cfg(test)]
+mod tests;
+
+/// A named set of CVar values, applied as a batch.
+#[derive(Debug, Clone)]
+pub struct CVarPreset {
+    name: String,
+    description: String,
+    values: Vec<(String, Value)>,
+}
+
+impl CVarPreset {
+    /// Creates a new, empty preset. The group of the preset is the part of its name before the last `.`.
+    pub fn new(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            values: vec![],
+        }
+    }
+
+    /// Sets the value the preset has for the given CVar.
+    pub fn with_value(mut self, path: impl Into<String>, value: impl Into<Value>) -> Self {
+        let path = path.into();
+
+        self.values.retain(|(x, _)| *x != path);
+        self.values.push((path, value.into()));
+
+        self
+    }
+
+    /// Returns the name of the preset.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the group of the preset, being the part of its name before the last `.` (or nothing, if there's no `.`).
+    pub fn group(&self) -> &str {
+        self.name.rsplit_once('.').map(|x| x.0).unwrap_or("")
+    }
+
+    /// Returns the description of the preset.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Returns every CVar path and value in the preset, in the order they were added.
+    pub fn values(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Returns whether every CVar in the preset currently has the preset's value.
+    pub fn matches(&self, world: &World) -> Result<bool, CVarError> {
+        let management = world.resource::<CVarManagement>();
+        let registry = world.resource::<AppTypeRegistry>().read();
+
+        for (path, value) in self.values() {
+            let current = management.get_cvar_reflect(world, path)?;
+            let current = serialize_value(&registry, current.as_partial_reflect())?;
+
+            if !same_value(&current, value) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// The registered presets, in the order they were registered.
+#[derive(Resource, Default, Debug)]
+pub struct CVarPresets {
+    presets: Vec<CVarPreset>,
+}
+
+impl CVarPresets {
+    /// Registers a preset, replacing any existing preset with the same name.
+    pub fn register(&mut self, preset: CVarPreset) {
+        match self.presets.iter_mut().find(|x| x.name == preset.name) {
+            Some(existing) => *existing = preset,
+            None => self.presets.push(preset),
+        }
+    }
+
+    /// Returns the preset with the given name.
+    pub fn get(&self, name: &str) -> Option<&CVarPreset> {
+        self.presets.iter().find(|x| x.name == name)
+    }
+
+    /// Returns every preset, in the order they were registered.
+    pub fn iter(&self) -> impl Iterator<Item = &CVarPreset> {
+        self.presets.iter()
+    }
+
+    /// Returns every preset in the given group, in the order they were registered.
+    pub fn group<'a>(&'a self, group: &'a str) -> impl Iterator<Item = &'a CVarPreset> {
+        self.presets.iter().filter(move |x| x.group() == group)
+    }
+
+    /// Returns the first preset in the given group that the current values match, if any.
+    /// `None` means the values have diverged from every preset, i.e. "Custom".
+    /// # Remarks
+    /// Presets containing CVars that can't be read (i.e. aren't registered) never match.
+    pub fn active(&self, world: &World, group: &str) -> Option<&CVarPreset> {
+        self.presets
+            .iter()
+            .find(|x| x.group() == group && x.matches(world).unwrap_or(false))
+    }
+}
+
+/// Applies the preset with the given name.
+/// Every value is checked before any is applied, so a preset with a bad value doesn't end up partially applied.
+pub(crate) fn apply_preset(world: &mut World, name: &str) -> Result<(), CVarError> {
+    let preset = world
+        .get_resource::<CVarPresets>()
+        .and_then(|x| x.get(name))
+        .ok_or(CVarError::UnknownPreset)?
+        .clone();
+
+    world.resource_scope(|world, management: bevy_ecs::world::Mut<CVarManagement>| {
+        for (path, value) in preset.values() {
+            management.check_cvar_deserialize(world, path, value.clone().into_deserializer())?;
+        }
+
+        for (path, value) in preset.values() {
+            management.set_cvar_deserialize(world, path, value.clone().into_deserializer())?;
+        }
+
+        Ok(())
+    })
+}
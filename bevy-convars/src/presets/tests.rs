@@ -0,0 +1,97 @@
+use std::error::Error;
+
+use crate::{
+    AppExtensions, CVarError, WorldExtensions,
+    tests::{TestBool, TestInteger, TestVolume, make_test_app},
+};
+
+use super::{CVarPreset, CVarPresets};
+
+#[test]
+pub fn apply_and_detect_presets() -> Result<(), Box<dyn Error>> {
+    let mut app = make_test_app();
+
+    app.register_cvar_preset(
+        CVarPreset::new("testrig.low", "Low settings.")
+            .with_value("testrig.test_int", 1)
+            .with_value("testrig.test_bool", false),
+    );
+    app.register_cvar_preset(
+        CVarPreset::new("testrig.high", "High settings.")
+            .with_value("testrig.test_int", 10)
+            .with_value("testrig.test_bool", true),
+    );
+    app.register_cvar_preset(CVarPreset::new("other.preset", "Not in the group."));
+
+    let world = app.world_mut();
+
+    let presets = world.resource::<CVarPresets>();
+    assert_eq!(
+        presets
+            .group("testrig")
+            .map(|x| (x.name(), x.description()))
+            .collect::<Vec<_>>(),
+        [
+            ("testrig.low", "Low settings."),
+            ("testrig.high", "High settings.")
+        ]
+    );
+
+    world.apply_cvar_preset("testrig.low")?;
+
+    assert_eq!(**world.resource::<TestInteger>(), 1);
+    assert!(!**world.resource::<TestBool>());
+
+    let active = world
+        .resource::<CVarPresets>()
+        .active(world, "testrig")
+        .map(|x| x.name());
+    assert_eq!(active, Some("testrig.low"));
+
+    // Diverging from every preset is "Custom".
+    **world.resource_mut::<TestInteger>() = 5;
+
+    assert!(
+        world
+            .resource::<CVarPresets>()
+            .active(world, "testrig")
+            .is_none()
+    );
+
+    world.apply_cvar_preset("testrig.high")?;
+
+    let active = world
+        .resource::<CVarPresets>()
+        .active(world, "testrig")
+        .map(|x| x.name());
+    assert_eq!(active, Some("testrig.high"));
+
+    assert!(matches!(
+        world.apply_cvar_preset("testrig.missing"),
+        Err(CVarError::UnknownPreset)
+    ));
+
+    Ok(())
+}
+
+#[test]
+pub fn bad_preset_is_not_partially_applied() {
+    let mut app = make_test_app();
+
+    app.register_cvar_preset(
+        CVarPreset::new("testrig.broken", "Has an out of range value.")
+            .with_value("testrig.test_int", 3)
+            .with_value("testrig.derived.volume", 2.0),
+    );
+
+    let world = app.world_mut();
+    let default = **world.resource::<TestInteger>();
+
+    assert!(matches!(
+        world.apply_cvar_preset("testrig.broken"),
+        Err(CVarError::ConstraintViolation(_))
+    ));
+
+    assert_eq!(**world.resource::<TestInteger>(), default);
+    assert_eq!(**world.resource::<TestVolume>(), 0.5);
+}
@@ -1,9 +1,13 @@
 //! Contains types for reflecting over CVars statically and dynamically.
 
-use std::any::TypeId;
+use std::{any::TypeId, fmt::Display, marker::PhantomData, ops::RangeBounds, str::FromStr};
 
 use bevy_ecs::{change_detection::DetectChanges, prelude::Resource, world::Ref};
-use bevy_reflect::{FromType, PartialReflect, Reflect};
+use bevy_reflect::{
+    FromReflect, FromType, GetTypeRegistration, PartialReflect, Reflect, ReflectDeserialize,
+    ReflectSerialize, TypePath, TypeRegistry,
+};
+use serde::{Serialize, de::DeserializeOwned};
 
 use crate::{CVarError, CVarFlags};
 
@@ -22,6 +26,25 @@ pub trait CVarMeta:
     /// Sets the CVar to default directly, without modifying other properties.
     /// This does not reset the CVar's "is default" state in any way and simply modifies the CVar.
     fn set_to_default(&mut self);
+
+    /// A human readable description of the CVar.
+    const DESCRIPTION: &'static str = "";
+    /// Alternative paths the CVar can be found under in configs, i.e. paths it was previously known as.
+    const ALIASES: &'static [&'static str] = &[];
+    /// The range of values allowed for the CVar as written in its declaration (i.e. `0.0..=1.0`), if it's restricted to one.
+    /// This is only descriptive, the range itself is enforced by [CVarMeta::validate].
+    const RANGE: Option<&'static str> = None;
+
+    /// Checks whether a value is allowed for this CVar, returning a description of the problem if not.
+    /// # Remarks
+    /// This is enforced when setting the CVar through reflection (i.e. [WorldExtensions](crate::WorldExtensions) and config loading), not when it's modified directly.
+    fn validate(_value: &Self::Inner) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Registers optional type data for the CVar's value type. Implemented by the macros, don't implement this yourself!
+    #[doc(hidden)]
+    fn register_value_type_data(_registry: &mut TypeRegistry) {}
 }
 
 /// Provides bevy reflection metadata for CVars.
@@ -32,8 +55,12 @@ pub struct ReflectCVar {
         for<'a> fn(&'a mut dyn PartialReflect) -> Result<&'a mut dyn PartialReflect, CVarError>,
     default_inner: fn() -> Box<dyn PartialReflect>,
     is_default_value: fn(Ref<dyn PartialReflect>) -> bool,
+    validate: fn(&dyn PartialReflect) -> Result<(), String>,
     inner_type: TypeId,
     path: &'static str,
+    aliases: &'static [&'static str],
+    range: Option<&'static str>,
+    description: &'static str,
     flags: CVarFlags,
 }
 
@@ -48,6 +75,21 @@ impl ReflectCVar {
         self.path
     }
 
+    /// Returns the alternative paths of the CVar.
+    pub fn aliases(&self) -> &'static [&'static str] {
+        self.aliases
+    }
+
+    /// Returns the range of values allowed for the CVar, see [CVarMeta::RANGE].
+    pub fn range(&self) -> Option<&'static str> {
+        self.range
+    }
+
+    /// Returns the description of the CVar.
+    pub fn description(&self) -> &'static str {
+        self.description
+    }
+
     /// Returns the CVar's flags.
     pub fn flags(&self) -> CVarFlags {
         self.flags
@@ -69,16 +111,48 @@ impl ReflectCVar {
         (self.reflect_inner_mut)(cvar)
     }
 
-    /// Apply a reflected value to the CVar.
+    /// Apply a reflected value to the CVar, replacing its current value (lists and maps aren't merged into the existing ones).
+    /// # Remarks
+    /// The value is checked against the CVar's [constraints](CVarMeta::validate) before being applied, and the CVar is left unmodified if it's rejected.
     pub fn reflect_apply(
         &self,
         cvar: &mut dyn PartialReflect,
         value: &dyn PartialReflect,
     ) -> Result<(), CVarError> {
-        let inner_mut = self.reflect_inner_mut(cvar)?;
+        let candidate = self.build_inner(value)?;
 
-        inner_mut.try_apply(value)?;
-        Ok(())
+        (self.validate)(candidate.as_partial_reflect()).map_err(CVarError::ConstraintViolation)?;
+
+        self.reflect_inner_mut(cvar)?
+            .try_as_reflect_mut()
+            .ok_or(CVarError::BadCVarType)?
+            .set(candidate)
+            .map_err(|_| CVarError::BadCVarType)
+    }
+
+    /// Builds a new instance of the CVar's value type from the given value.
+    /// Dynamic values (i.e. those deserialized without [FromReflect] support) are applied over the CVar's default value.
+    fn build_inner(&self, value: &dyn PartialReflect) -> Result<Box<dyn Reflect>, CVarError> {
+        let cloned = value
+            .try_as_reflect()
+            .filter(|x| x.as_any().type_id() == self.inner_type)
+            .and_then(|x| x.reflect_clone().ok());
+
+        if let Some(cloned) = cloned {
+            return Ok(cloned);
+        }
+
+        let mut candidate = self.default_inner();
+        candidate.try_apply(value)?;
+
+        candidate
+            .try_into_reflect()
+            .map_err(|_| CVarError::BadCVarType)
+    }
+
+    /// Checks whether the given value is allowed for the CVar, see [CVarMeta::validate].
+    pub fn validate(&self, value: &dyn PartialReflect) -> Result<(), String> {
+        (self.validate)(value)
     }
 
     /// Returns an instance of the CVar's default value.
@@ -96,26 +170,131 @@ impl<T: CVarMeta> FromType<T> for ReflectCVar {
     fn from_type() -> Self {
         ReflectCVar {
             inner_type: std::any::TypeId::of::<T::Inner>(),
-            // TODO: Make these less reflective by adding functions to CVarMeta.
             reflect_inner: |r| {
-                r.reflect_ref()
-                    .as_tuple_struct()
-                    .map_err(|_| CVarError::BadCVarType)?
-                    .field(0)
+                r.try_downcast_ref::<T>()
+                    .map(|x| &**x as &dyn PartialReflect)
                     .ok_or(CVarError::BadCVarType)
             },
 
             reflect_inner_mut: |r| {
-                r.reflect_mut()
-                    .as_tuple_struct()
-                    .map_err(|_| CVarError::BadCVarType)?
-                    .field_mut(0)
+                r.try_downcast_mut::<T>()
+                    .map(|x| &mut **x as &mut dyn PartialReflect)
                     .ok_or(CVarError::BadCVarType)
             },
             default_inner: || Box::new(T::default_inner()),
             is_default_value: |r| r.added() == r.last_changed(),
+            validate: |value| {
+                let value = value
+                    .try_downcast_ref::<T::Inner>()
+                    .ok_or_else(|| "Value is not of the CVar's type.".to_owned())?;
+
+                T::validate(value)
+            },
             path: T::CVAR_PATH,
+            aliases: T::ALIASES,
+            range: T::RANGE,
+            description: T::DESCRIPTION,
             flags: T::flags(),
         }
     }
 }
+
+/// Provides bevy reflection metadata for converting a value to and from a string, using its [FromStr] and [Display] implementations.
+/// # Remarks
+/// This is registered automatically for the value type of CVars declared with the provided macros, if it implements both traits.
+/// It's used as a fallback for saving, loading and setting CVars whose types can't be serialized or deserialized through reflection.
+#[derive(Clone)]
+pub struct ReflectCVarString {
+    from_str: fn(&str) -> Result<Box<dyn PartialReflect>, String>,
+    to_string: fn(&dyn PartialReflect) -> Option<String>,
+}
+
+impl ReflectCVarString {
+    /// Parses a new instance of the type from the given string.
+    pub fn from_str(&self, s: &str) -> Result<Box<dyn PartialReflect>, String> {
+        (self.from_str)(s)
+    }
+
+    /// Formats the given value as a string, returning [None] if the value isn't of the expected type.
+    pub fn to_string(&self, value: &dyn PartialReflect) -> Option<String> {
+        (self.to_string)(value)
+    }
+}
+
+impl<T: FromStr + Display + PartialReflect> FromType<T> for ReflectCVarString
+where
+    T::Err: Display,
+{
+    fn from_type() -> Self {
+        ReflectCVarString {
+            from_str: |s| {
+                T::from_str(s)
+                    .map(|x| Box::new(x) as Box<dyn PartialReflect>)
+                    .map_err(|e| e.to_string())
+            },
+            to_string: |value| value.try_downcast_ref::<T>().map(T::to_string),
+        }
+    }
+}
+
+/// Internal function used by the derive macro to check a value against a range. Don't use this!
+#[doc(hidden)]
+pub fn range_contains<T: PartialOrd, R: RangeBounds<T>>(range: R, value: &T) -> bool {
+    range.contains(value)
+}
+
+/// Internal type used by the macros to register optional type data for a CVar's value type, depending on the traits it implements.
+/// Don't use this!
+#[doc(hidden)]
+pub struct TypeDataProbe<T>(PhantomData<T>);
+
+impl<T> TypeDataProbe<T> {
+    #[doc(hidden)]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[doc(hidden)]
+pub trait RegisterSerde {
+    fn register_serde(&self, registry: &mut TypeRegistry);
+}
+
+impl<T: FromReflect + TypePath + GetTypeRegistration + Serialize + DeserializeOwned> RegisterSerde
+    for TypeDataProbe<T>
+{
+    fn register_serde(&self, registry: &mut TypeRegistry) {
+        registry.register_type_data::<T, ReflectSerialize>();
+        registry.register_type_data::<T, ReflectDeserialize>();
+    }
+}
+
+#[doc(hidden)]
+pub trait RegisterSerdeFallback {
+    fn register_serde(&self, _registry: &mut TypeRegistry) {}
+}
+
+impl<T> RegisterSerdeFallback for &TypeDataProbe<T> {}
+
+#[doc(hidden)]
+pub trait RegisterString {
+    fn register_string(&self, registry: &mut TypeRegistry);
+}
+
+impl<T: Reflect + TypePath + GetTypeRegistration + FromStr + Display> RegisterString
+    for TypeDataProbe<T>
+where
+    T::Err: Display,
+{
+    fn register_string(&self, registry: &mut TypeRegistry) {
+        registry.register_type_data::<T, ReflectCVarString>();
+    }
+}
+
+#[doc(hidden)]
+pub trait RegisterStringFallback {
+    fn register_string(&self, _registry: &mut TypeRegistry) {}
+}
+
+impl<T> RegisterStringFallback for &TypeDataProbe<T> {}
@@ -1,4 +1,9 @@
-//! Provides support for saving CVars to a TOML config file.
+//! Provides support for saving CVars to a TOML config file (or one in another [format](crate::format)).
+
+use std::marker::PhantomData;
+use std::time::Duration;
+#[cfg(feature = "config_loader")]
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use bevy_ecs::{
     change_detection::MaybeLocation,
@@ -6,18 +11,182 @@ use bevy_ecs::{
     reflect::{AppTypeRegistry, ReflectResource},
     world::{Ref, World},
 };
-use bevy_reflect::{Reflect, ReflectSerialize};
+use bevy_log::warn;
+use bevy_reflect::{
+    PartialReflect, Reflect, ReflectKind, ReflectSerialize, TypeInfo, TypeRegistry, VariantInfo,
+    serde::TypedReflectSerializer,
+};
 use serde::Serialize;
-use toml_edit::{DocumentMut, Item, Table, ser::ValueSerializer};
+use toml_edit::{Decor, DocumentMut, Item, Table, TableLike, ser::ValueSerializer};
 
+#[cfg(feature = "config_loader")]
+use crate::loader::ORPHAN_METADATA_KEY;
 use crate::{
     CVarError, CVarFlags, CVarManagement,
-    reflect::{CVarMeta, ReflectCVar},
+    format::{ConfigFormat, TomlFormat},
+    layers::same_value,
+    reflect::{CVarMeta, ReflectCVar, ReflectCVarString},
 };
 
 #[cfg(test)]
 mod tests;
 
+/// Serializes a CVar's value into a TOML value.
+/// # Remarks
+/// Types with serde support, and types reflection can serialize structurally (structs, lists, enums, etc), are serialized through reflection.
+/// Opaque types without serde support are instead written as a string using [ReflectCVarString], if available.
+pub fn serialize_value(
+    registry: &TypeRegistry,
+    value: &dyn PartialReflect,
+) -> Result<toml_edit::Value, CVarError> {
+    let registration = value
+        .get_represented_type_info()
+        .and_then(|x| registry.get(x.type_id()))
+        .ok_or(CVarError::BadCVarType)?;
+
+    if registration.data::<ReflectSerialize>().is_none()
+        && registration.type_info().kind() == ReflectKind::Opaque
+    {
+        return registration
+            .data::<ReflectCVarString>()
+            .and_then(|x| x.to_string(value))
+            .map(toml_edit::Value::from)
+            .ok_or(CVarError::CannotSerialize);
+    }
+
+    Ok(TypedReflectSerializer::new(value, registry).serialize(ValueSerializer::new())?)
+}
+
+/// The prefix of the comments generated by [CVarSaveContext::with_comments]. Comments starting with it are replaced when saving again.
+pub const GENERATED_COMMENT_PREFIX: &str = "##";
+
+/// Returns the lines of the comment describing a CVar, see [CVarSaveContext::with_comments].
+fn cvar_comments(registry: &TypeRegistry, cvar: &ReflectCVar) -> Vec<String> {
+    let mut lines: Vec<String> = cvar
+        .description()
+        .lines()
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(str::to_owned)
+        .collect();
+
+    if let Ok(default) = serialize_value(registry, cvar.default_inner().as_ref()) {
+        lines.push(format!("Default: {}", default.to_string().trim()));
+    }
+
+    if let Some(range) = cvar.range() {
+        lines.push(format!("Range: {range}"));
+    }
+
+    // Enums with only unit variants are saved as the name of the variant.
+    let choices = match registry.get_type_info(cvar.inner_type()) {
+        Some(TypeInfo::Enum(info)) if info.iter().all(|x| matches!(x, VariantInfo::Unit(_))) => {
+            info.variant_names()
+        }
+        _ => &[],
+    };
+
+    if !choices.is_empty() {
+        let choices: Vec<String> = choices
+            .iter()
+            .map(|x| toml_edit::Value::from(*x).to_string())
+            .collect();
+
+        lines.push(format!("Choices: {}", choices.join(", ")));
+    }
+
+    lines.push(format!("Flags: {}", cvar.flags()));
+
+    lines
+}
+
+/// The value of a [SAVED](CVarFlags::SAVED) CVar, as found by [saved_cvars].
+pub(crate) enum SavedValue {
+    /// The CVar was never changed from its default value.
+    Untouched,
+    /// The CVar was changed, but its value is equal to its default value.
+    Default(toml_edit::Value),
+    /// The CVar was changed to a value other than its default value.
+    Changed(toml_edit::Value),
+}
+
+/// A [SAVED](CVarFlags::SAVED) CVar, as found by [saved_cvars].
+pub(crate) struct SavedCVar {
+    /// The reflection metadata of the CVar.
+    pub cvar: ReflectCVar,
+    /// The value of the CVar.
+    pub value: SavedValue,
+}
+
+/// Serializes the value of every [SAVED](CVarFlags::SAVED) CVar in the world.
+/// CVars that can't be serialized (see [serialize_value]) are skipped with a warning.
+pub(crate) fn saved_cvars(world: &World) -> Result<Vec<SavedCVar>, CVarError> {
+    let management: &CVarManagement = world.resource::<CVarManagement>();
+    let registry = world.resource::<AppTypeRegistry>().read();
+    let types = management.iterate_cvar_types();
+
+    let mut cvars = vec![];
+
+    for reg in types {
+        let cvar = reg.data::<ReflectCVar>().expect("Impossible.");
+
+        if !cvar.flags().contains(CVarFlags::SAVED) {
+            continue;
+        }
+
+        let resource = reg.data::<ReflectResource>().expect("Impossible.");
+
+        let cvar_id = management.tree.get(cvar.cvar_path()).unwrap();
+
+        let change_data = world.get_resource_change_ticks_by_id(cvar_id).unwrap();
+
+        let caller = MaybeLocation::caller();
+
+        let res = resource.reflect(world)?;
+        let resource: Ref<dyn Reflect> = {
+            // Jank, Bevy is missing an API for this..
+
+            Ref::new(
+                res,
+                &change_data.added,
+                &change_data.changed,
+                Tick::new(0),
+                Tick::new(0),
+                caller.as_ref(),
+            )
+        };
+
+        let value = if cvar.is_default_value(resource) {
+            SavedValue::Untouched
+        } else {
+            let inner = cvar.reflect_inner(res.as_partial_reflect())?;
+
+            let value = match serialize_value(&registry, inner) {
+                Ok(value) => value,
+                Err(e) => {
+                    warn!("Couldn't save CVar {}, got error: {e}", cvar.cvar_path());
+                    continue;
+                }
+            };
+
+            let default = serialize_value(&registry, cvar.default_inner().as_ref());
+
+            if default.is_ok_and(|x| same_value(&x, &value)) {
+                SavedValue::Default(value)
+            } else {
+                SavedValue::Changed(value)
+            }
+        };
+
+        cvars.push(SavedCVar {
+            cvar: cvar.clone(),
+            value,
+        });
+    }
+
+    Ok(cvars)
+}
+
 /// Provides a context for mutating a TOML document to save CVars to it.
 ///
 /// # Example
@@ -34,22 +203,96 @@ mod tests;
 /// // And serialize out the results so we can save it.
 /// let file_contents = context.to_string();
 /// ```
-pub struct CVarSaveContext(DocumentMut);
+///
+/// # Formats
+/// The document is always TOML, and is converted when serialized for contexts using another format (see [CVarSaveContext::with_format]).
+pub struct CVarSaveContext<F = TomlFormat> {
+    document: DocumentMut,
+    /// How long orphaned keys are kept before being pruned, if at all.
+    orphan_max_age: Option<Duration>,
+    /// Whether CVars at their default value are removed from the document.
+    remove_defaults: bool,
+    /// Whether each saved CVar is commented with its description and metadata.
+    comments: bool,
+    /// The format the document is serialized in.
+    format: PhantomData<F>,
+}
 
 impl CVarSaveContext {
     /// Creates a new context with an empty document.
     pub fn blank() -> Self {
-        Self(DocumentMut::new())
+        Self::from_document(DocumentMut::new())
     }
 
     /// Creates a new context with an existing document.
     pub fn from_document(doc: DocumentMut) -> Self {
-        Self(doc)
+        Self {
+            document: doc,
+            orphan_max_age: None,
+            remove_defaults: false,
+            comments: false,
+            format: PhantomData,
+        }
+    }
+}
+
+impl<F: ConfigFormat> CVarSaveContext<F> {
+    /// Sets the format the document is serialized in by [CVarSaveContext::serialize].
+    pub fn with_format<G: ConfigFormat>(self) -> CVarSaveContext<G> {
+        CVarSaveContext {
+            document: self.document,
+            orphan_max_age: self.orphan_max_age,
+            remove_defaults: self.remove_defaults,
+            comments: self.comments,
+            format: PhantomData,
+        }
+    }
+
+    /// Serializes the document in the context's format.
+    pub fn serialize(&self) -> Result<String, CVarError> {
+        Ok(F::serialize(&self.document)?)
+    }
+
+    /// Sets how long orphaned keys from the user config are kept around, after which they're pruned when saving.
+    /// By default orphans are kept forever.
+    pub fn with_orphan_max_age(self, max_age: Duration) -> Self {
+        Self {
+            orphan_max_age: Some(max_age),
+            ..self
+        }
+    }
+
+    /// Sets whether [CVarSaveContext::save_world] removes the entries of CVars at their default value from the document,
+    /// instead of leaving them as-is. This includes CVars that were changed back to a value equal to their default.
+    /// Tables left empty are removed too, while unrelated keys and comments are kept.
+    ///
+    /// This is meant for saving over the existing user config, where a setting reset to its default would otherwise keep its old entry.
+    /// The user's config is saved this way when set in its [UserConfigSaveOptions](crate::storage::UserConfigSaveOptions).
+    /// # Example
+    /// ```
+    /// # use bevy_convars::save::CVarSaveContext;
+    /// # let existing = "[render]\nssao = false\n";
+    /// let context = CVarSaveContext::from_document(existing.parse().unwrap()).with_remove_defaults(true);
+    /// ```
+    pub fn with_remove_defaults(self, remove_defaults: bool) -> Self {
+        Self {
+            remove_defaults,
+            ..self
+        }
+    }
+
+    /// Sets whether [CVarSaveContext::save_world] comments each CVar it saves with its description, default value, allowed range or choices and flags,
+    /// so the config is self-explanatory when edited by hand.
+    ///
+    /// Generated comments start with [GENERATED_COMMENT_PREFIX] and are replaced when saving again, while any other comments are kept.
+    /// CVars written as dotted keys or within inline tables aren't commented, as comments can't be placed there.
+    pub fn with_comments(self, comments: bool) -> Self {
+        Self { comments, ..self }
     }
 
     /// Returns the document used from the context, destroying the context.
     pub fn return_document(self) -> DocumentMut {
-        self.0
+        self.document
     }
 
     fn get_cvar_entry(&mut self, path: &str) -> Result<toml_edit::Entry<'_>, CVarError> {
@@ -58,7 +301,7 @@ impl CVarSaveContext {
         let leading_sections = sections.clone().take(section_count - 1);
         let final_section = sections.next_back().unwrap();
 
-        let mut cur_table = self.0.as_table_mut();
+        let mut cur_table = self.document.as_table_mut();
 
         for section in leading_sections {
             cur_table = cur_table
@@ -71,29 +314,254 @@ impl CVarSaveContext {
         Ok(cur_table.entry(final_section))
     }
 
-    /// Saves an individual CVar to the document.
-    fn save_cvar_inner(&mut self, path: &str, value: &impl Serialize) -> Result<(), CVarError> {
-        let entry = self.get_cvar_entry(path)?;
+    /// Returns whether the document contains a value at the given path.
+    #[cfg(feature = "config_loader")]
+    fn contains_path(&self, path: &str) -> bool {
+        let mut cur: &dyn TableLike = self.document.as_table();
+
+        let mut sections = path.split('.').peekable();
 
-        *entry.or_insert(toml_edit::Item::None) =
-            Item::Value(value.serialize(ValueSerializer::new())?);
+        while let Some(section) = sections.next() {
+            let Some(item) = cur.get(section) else {
+                return false;
+            };
+
+            if sections.peek().is_none() {
+                return true;
+            }
+
+            let Some(table) = item.as_table_like() else {
+                return false;
+            };
+
+            cur = table;
+        }
+
+        false
+    }
+
+    /// Replaces the generated comments of the entry at the given path with the given lines, keeping any other comments.
+    fn set_comments(&mut self, path: &str, lines: &[String]) {
+        /// Merges the lines into the existing comments in front of an entry.
+        fn merge(decor: &mut Decor, lines: &[String], default: &str) {
+            let existing = decor
+                .prefix()
+                .and_then(|x| x.as_str())
+                .unwrap_or(default)
+                .to_owned();
+
+            let mut prefix = String::new();
+            let mut indent = "";
+
+            for line in existing.split_inclusive('\n') {
+                if !line.ends_with('\n') {
+                    indent = line;
+                } else if !line.trim_start().starts_with(GENERATED_COMMENT_PREFIX) {
+                    prefix.push_str(line);
+                }
+            }
+
+            for line in lines {
+                prefix.push_str(&format!("{GENERATED_COMMENT_PREFIX} {line}\n"));
+            }
+
+            prefix.push_str(indent);
+            decor.set_prefix(prefix);
+        }
+
+        let (leading, last) = path.rsplit_once('.').unwrap_or(("", path));
+
+        let mut cur = self.document.as_table_mut();
+
+        for section in leading.split('.').filter(|x| !x.is_empty()) {
+            // Comments can't be placed within inline tables, nor within dotted keys.
+            let Some(table) = cur.get_mut(section).and_then(Item::as_table_mut) else {
+                return;
+            };
+
+            if table.is_dotted() {
+                return;
+            }
+
+            cur = table;
+        }
+
+        match cur.get_mut(last) {
+            Some(Item::Table(table)) if !table.is_dotted() => merge(table.decor_mut(), lines, "\n"),
+            Some(Item::ArrayOfTables(array)) => {
+                if let Some(table) = array.get_mut(0) {
+                    merge(table.decor_mut(), lines, "\n");
+                }
+            }
+            Some(Item::Value(_)) => {
+                if let Some(mut key) = cur.key_mut(last) {
+                    merge(key.leaf_decor_mut(), lines, "");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Removes the value at the given path from the document, if present, along with any tables left empty by its removal.
+    /// Tables with comments are kept, as those were likely written by hand.
+    fn remove_path_pruned(&mut self, path: &str) {
+        /// Returns whether the decor holds any comments.
+        fn has_comments(decor: &Decor) -> bool {
+            [decor.prefix(), decor.suffix()]
+                .into_iter()
+                .flatten()
+                .any(|x| x.as_str().is_some_and(|x| x.contains('#')))
+        }
+
+        /// Returns whether the table at the given key has any comments in front of or after it.
+        fn commented(table: &dyn TableLike, key: &str) -> bool {
+            let decor = match table.get(key) {
+                Some(Item::Table(table)) => Some(table.decor()),
+                Some(Item::Value(toml_edit::Value::InlineTable(table))) => Some(table.decor()),
+                _ => None,
+            };
+
+            decor.is_some_and(has_comments)
+                || table.key(key).is_some_and(|x| has_comments(x.leaf_decor()))
+        }
+
+        /// Returns whether the value was removed.
+        fn remove(table: &mut dyn TableLike, sections: &[&str]) -> bool {
+            let [section, rest @ ..] = sections else {
+                return false;
+            };
+
+            if rest.is_empty() {
+                return table.remove(section).is_some();
+            }
+
+            let Some(child) = table.get_mut(section).and_then(|x| x.as_table_like_mut()) else {
+                return false;
+            };
+
+            if !remove(child, rest) {
+                return false;
+            }
+
+            if child.is_empty() && !commented(table, section) {
+                table.remove(section);
+            }
+
+            true
+        }
+
+        let sections: Vec<&str> = path.split('.').collect();
+
+        remove(self.document.as_table_mut(), &sections);
+    }
+
+    /// Writes the world's orphaned user config keys back into the document, pruning those that are too old or were purged.
+    #[cfg(feature = "config_loader")]
+    fn save_orphans(&mut self, management: &CVarManagement) -> Result<(), CVarError> {
+        let now = SystemTime::now();
+
+        for path in management.purged_orphans.iter() {
+            self.remove_path_pruned(path);
+        }
+
+        let mut orphans: Vec<_> = management.orphans().collect();
+        orphans.sort_by_key(|(path, _)| *path);
+
+        let mut metadata = Table::new();
+
+        for (path, orphan) in orphans {
+            let since = orphan.orphaned_since.unwrap_or(now);
+            let age = now.duration_since(since).unwrap_or_default();
+
+            if self.orphan_max_age.is_some_and(|x| age > x) {
+                self.remove_path_pruned(path);
+                continue;
+            }
+
+            // Orphans already within the document are left untouched.
+            if !self.contains_path(path) {
+                self.save_value(path, orphan.value.clone())?;
+            }
+
+            let secs = since
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            metadata.insert(path, toml_edit::value(secs as i64));
+        }
+
+        let root = self.document.as_table_mut();
+
+        if metadata.is_empty() {
+            root.remove(ORPHAN_METADATA_KEY);
+        } else if let Some(existing) = root
+            .get_mut(ORPHAN_METADATA_KEY)
+            .and_then(Item::as_table_mut)
+        {
+            // Keep the placement of the existing table.
+            *metadata.decor_mut() = existing.decor().clone();
+            if let Some(position) = existing.position() {
+                metadata.set_position(position);
+            }
+
+            *existing = metadata;
+        } else {
+            root.insert(ORPHAN_METADATA_KEY, Item::Table(metadata));
+        }
 
         Ok(())
     }
 
-    fn save_cvar_inner_erased(
+    /// Writes a serialized value into the document at the given path.
+    /// # Remarks
+    /// Tables and lists of tables are written as standard tables (`[a.b]`) and arrays of tables (`[[a.b]]`),
+    /// unless the document already contains the CVar as an inline value, in which case that layout is kept.
+    pub(crate) fn save_value(
         &mut self,
         path: &str,
-        value: &bevy_reflect::serde::Serializable,
+        value: toml_edit::Value,
     ) -> Result<(), CVarError> {
         let entry = self.get_cvar_entry(path)?;
-
-        *entry.or_insert(toml_edit::Item::None) =
-            Item::Value(value.serialize(ValueSerializer::new())?);
+        let item = entry.or_insert(Item::None);
+
+        *item = match item {
+            Item::Value(_) => Item::Value(value),
+            Item::Table(existing) => match Item::Value(value).into_table() {
+                Ok(mut table) => {
+                    // Keep the comments and placement of the table we're replacing.
+                    *table.decor_mut() = existing.decor().clone();
+                    if let Some(position) = existing.position() {
+                        table.set_position(position);
+                    }
+
+                    Item::Table(table)
+                }
+                Err(value) => value,
+            },
+            Item::None | Item::ArrayOfTables(_) => Self::standard_layout(value),
+        };
 
         Ok(())
     }
 
+    /// Converts a value into its non-inline layout, if it has one.
+    fn standard_layout(value: toml_edit::Value) -> Item {
+        let item = match Item::Value(value).into_table() {
+            Ok(table) => return Item::Table(table),
+            Err(item) => item,
+        };
+
+        match item.into_array_of_tables() {
+            Ok(array) => Item::ArrayOfTables(array),
+            Err(item) => item,
+        }
+    }
+
+    /// Saves an individual CVar to the document.
+    fn save_cvar_inner(&mut self, path: &str, value: &impl Serialize) -> Result<(), CVarError> {
+        self.save_value(path, value.serialize(ValueSerializer::new())?)
+    }
+
     /// Manually save an individual CVar to the document.
     /// # Remarks
     /// This does not check for the presence of [CVarFlags::SAVED], and as such can be used to specially handle some CVars.
@@ -117,62 +585,40 @@ impl CVarSaveContext {
     /// Saves a world's CVars to the document.
     /// # Remarks
     /// This obeys [CVarFlags::SAVED] and will not attempt to save CVars without it.
+    ///
+    /// CVars that can't be serialized (see [serialize_value]) are skipped with a warning.
+    ///
+    /// Orphaned keys from the user config (see [CVarManagement::orphans]) are written back untouched, unless they were purged
+    /// or are older than the limit set with [CVarSaveContext::with_orphan_max_age].
+    ///
+    /// Entries of CVars at their default value are left as-is, unless set otherwise with [CVarSaveContext::with_remove_defaults].
     pub fn save_world(&mut self, world: &World) -> Result<(), CVarError> {
-        let management: &CVarManagement = world.resource::<CVarManagement>();
+        let saved = saved_cvars(world)?;
         let registry = world.resource::<AppTypeRegistry>().read();
-        let types = management.iterate_cvar_types();
 
-        for reg in types {
-            let cvar = reg.data::<ReflectCVar>().expect("Impossible.");
-
-            if !cvar.flags().contains(CVarFlags::SAVED) {
-                continue;
+        for SavedCVar { cvar, value } in saved {
+            let path = cvar.cvar_path();
+
+            match value {
+                SavedValue::Untouched | SavedValue::Default(_) if self.remove_defaults => {
+                    for path in std::iter::once(path).chain(cvar.aliases().iter().copied()) {
+                        self.remove_path_pruned(path);
+                    }
+                }
+                SavedValue::Untouched => {}
+                SavedValue::Default(value) | SavedValue::Changed(value) => {
+                    self.save_value(path, value)?;
+
+                    if self.comments {
+                        self.set_comments(path, &cvar_comments(&registry, &cvar));
+                    }
+                }
             }
-
-            let Some(serialize) = registry.get_type_data::<ReflectSerialize>(cvar.inner_type())
-            else {
-                panic!(
-                    "Can't save a saveable cvar due to lack of ReflectSerialize implementation. CVar in question is {}",
-                    cvar.cvar_path()
-                );
-            };
-
-            let resource = reg.data::<ReflectResource>().expect("Impossible.");
-
-            let cvar_id = management.tree.get(cvar.cvar_path()).unwrap();
-
-            let change_data = world.get_resource_change_ticks_by_id(cvar_id).unwrap();
-
-            let caller = MaybeLocation::caller();
-
-            let res = resource.reflect(world)?;
-            let resource: Ref<dyn Reflect> = {
-                // Jank, Bevy is missing an API for this..
-
-                Ref::new(
-                    res,
-                    &change_data.added,
-                    &change_data.changed,
-                    Tick::new(0),
-                    Tick::new(0),
-                    caller.as_ref(),
-                )
-            };
-
-            if cvar.is_default_value(resource) {
-                continue;
-            }
-
-            self.save_cvar_inner_erased(
-                cvar.cvar_path(),
-                &serialize.get_serializable(
-                    cvar.reflect_inner(res.as_partial_reflect())?
-                        .try_as_reflect()
-                        .unwrap(),
-                ),
-            )?;
         }
 
+        #[cfg(feature = "config_loader")]
+        self.save_orphans(world.resource::<CVarManagement>())?;
+
         Ok(())
     }
 }
@@ -180,6 +626,6 @@ impl CVarSaveContext {
 #[allow(clippy::to_string_trait_impl)]
 impl ToString for CVarSaveContext {
     fn to_string(&self) -> String {
-        self.0.to_string()
+        self.document.to_string()
     }
 }
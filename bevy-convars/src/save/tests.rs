@@ -1,10 +1,15 @@
 use std::str::FromStr;
 
+use bevy_reflect::Reflect;
+use serde::{Deserialize, Serialize};
 use toml_edit::DocumentMut;
 
 use crate::{
-    CVarError,
-    tests::{self, TestBool, TestInteger},
+    AppExtensions, CVar, CVarError,
+    tests::{
+        self, TestBool, TestColor, TestColorValue, TestInteger, TestMap, TestReflectOnly,
+        TestReflectOnlyValue, TestStruct, TestStructList, TestStructValue, TestVolume,
+    },
 };
 
 #[test]
@@ -24,8 +29,6 @@ pub fn save_unmodified_world() -> Result<(), CVarError> {
 
 #[test]
 pub fn save_modified_world() -> Result<(), CVarError> {
-    let expected = include_str!("expected_cfg_1.toml").to_owned().replace("\r\n", "\n");
-
     let mut app = tests::make_test_app();
 
     {
@@ -42,7 +45,7 @@ pub fn save_modified_world() -> Result<(), CVarError> {
     let result = save_ctx.return_document().to_string();
 
     // can't depend on exact file match here, no existing config.
-    assert!(result.len() > 0);
+    assert!(!result.is_empty());
     assert!(result.contains("test_bool"));
     assert!(result.contains("test_int"));
     assert!(result.contains("true"));
@@ -76,3 +79,351 @@ pub fn save_over_existing_cfg() -> Result<(), CVarError> {
 
     Ok(())
 }
+
+fn set_table_cvars(world: &mut bevy_ecs::world::World) {
+    **world.resource_mut::<TestStruct>() = TestStructValue {
+        name: "new".to_owned(),
+        weight: 3,
+    };
+
+    **world.resource_mut::<TestStructList>() = vec![
+        TestStructValue {
+            name: "first".to_owned(),
+            weight: 1,
+        },
+        TestStructValue {
+            name: "second".to_owned(),
+            weight: 2,
+        },
+    ];
+
+    world
+        .resource_mut::<TestMap>()
+        .insert("alpha".to_owned(), 1);
+}
+
+#[test]
+#[cfg(feature = "config_loader")]
+pub fn round_trip_standard_tables() -> Result<(), CVarError> {
+    let mut app = tests::make_test_app();
+
+    set_table_cvars(app.world_mut());
+
+    let mut save_ctx = crate::save::CVarSaveContext::blank();
+
+    save_ctx.save_world(app.world())?;
+
+    let result = save_ctx.to_string();
+
+    assert!(result.contains("[testrig.test_struct]"));
+    assert!(result.contains("[testrig.test_map]"));
+    assert!(result.contains("[[testrig.test_struct_list]]"));
+
+    let mut loaded = tests::make_test_app();
+
+    crate::loader::ConfigLoader::default().apply_from_string(
+        loaded.world_mut(),
+        &result,
+        Some("round_trip.toml"),
+        true,
+    )?;
+
+    let (world, loaded) = (app.world(), loaded.world());
+
+    assert_eq!(
+        **world.resource::<TestStruct>(),
+        **loaded.resource::<TestStruct>()
+    );
+    assert_eq!(
+        **world.resource::<TestStructList>(),
+        **loaded.resource::<TestStructList>()
+    );
+    assert_eq!(
+        **world.resource::<TestMap>(),
+        **loaded.resource::<TestMap>()
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn save_over_existing_table() -> Result<(), CVarError> {
+    const INITIAL: &str = include_str!("existing_cfg_3.toml");
+    let expected = include_str!("expected_cfg_3.toml")
+        .to_owned()
+        .replace("\r\n", "\n");
+    let mut app = tests::make_test_app();
+
+    {
+        let world = app.world_mut();
+
+        **world.resource_mut::<TestInteger>() = 42;
+        **world.resource_mut::<TestStruct>() = TestStructValue {
+            name: "new".to_owned(),
+            weight: 3,
+        };
+    }
+
+    let document = DocumentMut::from_str(INITIAL)?;
+
+    let mut save_ctx = crate::save::CVarSaveContext::from_document(document);
+
+    save_ctx.save_world(app.world())?;
+
+    assert_eq!(save_ctx.to_string(), expected);
+
+    Ok(())
+}
+
+#[test]
+pub fn save_keeps_inline_tables() -> Result<(), CVarError> {
+    let mut app = tests::make_test_app();
+
+    set_table_cvars(app.world_mut());
+
+    let document = DocumentMut::from_str(
+        "[testrig]\ntest_struct = { name = \"old\", weight = 1 }\ntest_struct_list = []\ntest_map = {}\n",
+    )?;
+
+    let mut save_ctx = crate::save::CVarSaveContext::from_document(document);
+
+    save_ctx.save_world(app.world())?;
+
+    let result = save_ctx.to_string();
+
+    assert!(result.contains("test_struct = { name = \"new\", weight = 3 }"));
+    assert!(result.contains("test_map = { alpha = 1 }"));
+    assert!(!result.contains("[testrig.test_struct]"));
+    assert!(!result.contains("[[testrig.test_struct_list]]"));
+
+    Ok(())
+}
+
+#[test]
+pub fn save_non_serde_values() -> Result<(), CVarError> {
+    let mut app = tests::make_test_app();
+
+    {
+        let world = app.world_mut();
+
+        **world.resource_mut::<TestColor>() = TestColorValue(0x123456);
+        **world.resource_mut::<TestReflectOnly>() = TestReflectOnlyValue {
+            scale: 1.5,
+            label: "custom".to_owned(),
+        };
+    }
+
+    let mut save_ctx = crate::save::CVarSaveContext::blank();
+
+    save_ctx.save_world(app.world())?;
+
+    let result = save_ctx.to_string();
+
+    assert!(result.contains("test_color = \"#123456\""));
+    assert!(result.contains("[testrig.test_reflect_only]"));
+    assert!(result.contains("label = \"custom\""));
+
+    Ok(())
+}
+
+#[cfg(feature = "config_loader")]
+const ORPHANED_CFG: &str = "[testrig]\ntest_int = 3\n\n[disabled_mod]\nvolume = 0.5\n\n[disabled_mod.keys]\njump = \"space\"\n\n[_orphaned_since]\n\"disabled_mod.volume\" = 0\n";
+
+#[test]
+#[cfg(feature = "config_loader")]
+pub fn save_orphaned_keys() -> Result<(), CVarError> {
+    let mut app = tests::make_test_app();
+
+    crate::loader::ConfigLoader::default().apply_from_string(
+        app.world_mut(),
+        ORPHANED_CFG,
+        Some("user.toml"),
+        true,
+    )?;
+
+    let management = app.world().resource::<crate::CVarManagement>();
+    assert_eq!(management.orphans().count(), 2);
+
+    let mut save_ctx = crate::save::CVarSaveContext::blank();
+
+    save_ctx.save_world(app.world())?;
+
+    let result = save_ctx.return_document();
+
+    assert_eq!(result["testrig"]["test_int"].as_integer(), Some(3));
+    assert_eq!(result["disabled_mod"]["volume"].as_float(), Some(0.5));
+    assert_eq!(
+        result["disabled_mod"]["keys"]["jump"].as_str(),
+        Some("space")
+    );
+    assert_eq!(
+        result["_orphaned_since"]["disabled_mod.volume"].as_integer(),
+        Some(0)
+    );
+    assert!(result["_orphaned_since"]["disabled_mod.keys.jump"].is_integer());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "config_loader")]
+pub fn prune_and_purge_orphans() -> Result<(), CVarError> {
+    let mut app = tests::make_test_app();
+
+    crate::loader::ConfigLoader::default().apply_from_string(
+        app.world_mut(),
+        ORPHANED_CFG,
+        Some("user.toml"),
+        true,
+    )?;
+
+    // disabled_mod.volume has been orphaned since the unix epoch, so it's well past any reasonable age.
+    let mut save_ctx =
+        crate::save::CVarSaveContext::from_document(DocumentMut::from_str(ORPHANED_CFG)?)
+            .with_orphan_max_age(std::time::Duration::from_secs(60 * 60 * 24 * 30));
+
+    save_ctx.save_world(app.world())?;
+
+    let result = save_ctx.return_document();
+
+    assert!(result["disabled_mod"].get("volume").is_none());
+    assert_eq!(
+        result["disabled_mod"]["keys"]["jump"].as_str(),
+        Some("space")
+    );
+    assert!(
+        result["_orphaned_since"]
+            .get("disabled_mod.volume")
+            .is_none()
+    );
+
+    let mut management = app.world_mut().resource_mut::<crate::CVarManagement>();
+    assert_eq!(
+        management.purge_orphans(|path, _| path.starts_with("disabled_mod.")),
+        2
+    );
+    assert_eq!(management.orphans().count(), 0);
+
+    let mut save_ctx = crate::save::CVarSaveContext::from_document(result);
+
+    save_ctx.save_world(app.world())?;
+
+    let result = save_ctx.return_document();
+
+    // Tables left empty by purging are removed too.
+    assert!(result.get("disabled_mod").is_none());
+    assert!(result.get("_orphaned_since").is_none());
+
+    Ok(())
+}
+
+#[test]
+pub fn save_removing_defaults() -> Result<(), CVarError> {
+    const INITIAL: &str = "# Test CVars.\n[testrig]\n# The integer.\ntest_int = 4\n# Removed, as it's untouched.\ntest_bool = false\n\n[testrig.derived]\nvolume = 0.75\n\n[other]\n# Unrelated.\nunrelated = true\n";
+
+    let mut app = tests::make_test_app();
+
+    {
+        let world = app.world_mut();
+
+        **world.resource_mut::<TestInteger>() = 7;
+        // Changed, but back to its default value.
+        **world.resource_mut::<TestVolume>() = 0.5;
+    }
+
+    let mut save_ctx =
+        crate::save::CVarSaveContext::from_document(DocumentMut::from_str(INITIAL).unwrap())
+            .with_remove_defaults(true);
+
+    save_ctx.save_world(app.world())?;
+
+    assert_eq!(
+        save_ctx.to_string(),
+        "# Test CVars.\n[testrig]\n# The integer.\ntest_int = 7\n\n[other]\n# Unrelated.\nunrelated = true\n"
+    );
+
+    // Tables with comments are kept even once empty.
+    const COMMENTED: &str =
+        "[testrig]\ntest_int = 4\n\n# Derived values.\n[testrig.derived]\nvolume = 0.75\n";
+
+    let mut save_ctx =
+        crate::save::CVarSaveContext::from_document(DocumentMut::from_str(COMMENTED).unwrap())
+            .with_remove_defaults(true);
+
+    save_ctx.save_world(app.world())?;
+
+    assert_eq!(
+        save_ctx.to_string(),
+        "[testrig]\ntest_int = 7\n\n# Derived values.\n[testrig.derived]\n"
+    );
+
+    Ok(())
+}
+
+/// The quality of the test rig.
+#[derive(Reflect, Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum TestQualityValue {
+    Low,
+    High,
+}
+
+/// The test rig's quality.
+#[derive(Reflect, CVar, Clone, PartialEq)]
+#[cvar(path = "testrig.quality", flags = SAVED, default = TestQualityValue::High)]
+struct TestQuality(TestQualityValue);
+
+#[test]
+pub fn save_with_comments() -> Result<(), CVarError> {
+    const INITIAL: &str = "[testrig]\n# Mine, kept.\ntest_int = 4\n";
+
+    let mut app = tests::make_test_app();
+    app.register_cvar::<TestQuality>();
+
+    {
+        let world = app.world_mut();
+
+        **world.resource_mut::<TestInteger>() = 7;
+        **world.resource_mut::<TestVolume>() = 0.25;
+        **world.resource_mut::<TestQuality>() = TestQualityValue::Low;
+    }
+
+    let save = |document: &str| -> Result<String, CVarError> {
+        let mut save_ctx =
+            crate::save::CVarSaveContext::from_document(DocumentMut::from_str(document).unwrap())
+                .with_comments(true);
+
+        save_ctx.save_world(app.world())?;
+
+        Ok(save_ctx.to_string())
+    };
+
+    let saved = save(INITIAL)?;
+
+    assert_eq!(
+        saved,
+        r#"[testrig]
+# Mine, kept.
+## Default: -5
+## Flags: SAVED | RUNTIME
+test_int = 7
+## The test rig's quality.
+## Default: "High"
+## Choices: "Low", "High"
+## Flags: SAVED
+quality = "Low"
+
+[testrig.derived]
+## Test derived cvar
+## with a range.
+## Default: 0.5
+## Range: 0.0..=1.0
+## Flags: SAVED | RUNTIME
+volume = 0.25
+"#
+    );
+
+    // Saving again replaces the generated comments rather than repeating them.
+    assert_eq!(save(&saved)?, saved);
+
+    Ok(())
+}
@@ -0,0 +1,456 @@
+//! Provides Source engine style `.cfg` scripts, line-based lists of commands for setting CVars.
+//!
+//! ```text
+//! // Comments start with two slashes.
+//! set render.aa.method Fxaa
+//! render.ssao.enabled false   // `set` is optional.
+//! reset render.bloom.intensity
+//! exec graphics/high.cfg
+//! ```
+//!
+//! - `set path value` (or just `path value`, for paths containing a `.`) sets a CVar. Values are TOML, though bare words are accepted as strings.
+//! - `reset path` resets a CVar to its default value.
+//! - `exec file.cfg` runs another script in place, relative to the script running it.
+//!
+//! Scripts are either turned into a config layer with [CVarScript::to_layer], or run from a console with
+//! [WorldExtensions::exec_cvar_command](crate::WorldExtensions::exec_cvar_command), which sets values on a [LayerKind::Runtime] layer.
+//! The [CVarLoaderPlugin](crate::loader::CVarLoaderPlugin) runs [AUTOEXEC] at startup when given a script directory.
+
+use std::{fmt::Display, str::FromStr};
+
+use bevy_ecs::{reflect::AppTypeRegistry, world::World};
+use bevy_log::warn;
+use bevy_reflect::std_traits::ReflectDefault;
+use serde::de::IntoDeserializer;
+use toml_edit::Value;
+
+use crate::{
+    CVarError, CVarManagement,
+    layers::{ConfigLayer, LayerKind, SourceLocation, canonical_path},
+    reflect::ReflectCVar,
+    save::serialize_value,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// The script run at startup, from the script directory given to the [CVarLoaderPlugin](crate::loader::CVarLoaderPlugin).
+pub const AUTOEXEC: &str = "autoexec.cfg";
+
+/// The source of the [LayerKind::Runtime] layer console commands are run against.
+pub const CONSOLE_SOURCE: &str = "console";
+
+/// A command within a [CVarScript].
+#[derive(Debug, Clone)]
+pub enum ScriptCommand {
+    /// `set path value` (or `path value`, for paths containing a `.`), setting a CVar.
+    Set {
+        /// The path of the CVar.
+        path: String,
+        /// The value to set it to.
+        value: Value,
+    },
+    /// `reset path`, resetting a CVar to its default value.
+    Reset(String),
+    /// `exec file.cfg`, running another script in place.
+    Exec(String),
+}
+
+/// What went wrong with a line of a [CVarScript].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ScriptErrorKind {
+    /// The line starts with something that's neither a command nor a CVar path.
+    UnknownCommand(String),
+    /// The given command is missing an argument.
+    MissingArgument(&'static str),
+    /// The given CVar path isn't valid.
+    InvalidPath(String),
+    /// The script (or the one to execute) couldn't be read, for the given reason.
+    ReadFailed(String),
+    /// The script ended up executing itself, through the given chain of scripts.
+    ExecCycle(String),
+}
+
+/// An error within a [CVarScript], along with where it occurred.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptError {
+    /// The source of the script.
+    pub source: String,
+    /// The 1-based line the error occurred on, or 0 if it concerns the script as a whole (i.e. it couldn't be read).
+    pub line: usize,
+    /// What went wrong.
+    pub kind: ScriptErrorKind,
+}
+
+impl std::error::Error for ScriptError {}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)?;
+
+        if self.line != 0 {
+            write!(f, ":{}", self.line)?;
+        }
+
+        write!(f, ": ")?;
+
+        match &self.kind {
+            ScriptErrorKind::UnknownCommand(command) => write!(f, "unknown command {command}"),
+            ScriptErrorKind::MissingArgument(command) => {
+                write!(f, "missing argument for {command}")
+            }
+            ScriptErrorKind::InvalidPath(path) => write!(f, "invalid CVar path {path}"),
+            ScriptErrorKind::ReadFailed(reason) => write!(f, "couldn't read script: {reason}"),
+            ScriptErrorKind::ExecCycle(cycle) => write!(f, "exec cycle: {cycle}"),
+        }
+    }
+}
+
+impl FromStr for ScriptCommand {
+    type Err = ScriptErrorKind;
+
+    /// Parses a single command, without any comment.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (command, rest) = split_token(s);
+
+        match command {
+            "set" => {
+                let (path, value) = split_token(rest);
+
+                parse_set(path, value)
+            }
+            "reset" => {
+                let (path, _) = split_token(rest);
+
+                validate_path(path, "reset")?;
+
+                Ok(ScriptCommand::Reset(path.to_owned()))
+            }
+            "exec" => {
+                let path = rest.trim().trim_matches('"');
+
+                if path.is_empty() {
+                    return Err(ScriptErrorKind::MissingArgument("exec"));
+                }
+
+                Ok(ScriptCommand::Exec(path.to_owned()))
+            }
+            // Bare paths need a `.`, so mistyped commands aren't taken for CVars.
+            path if path.contains('.') && crate::path::validate_cvar_path(path).is_ok() => {
+                parse_set(path, rest)
+            }
+            command => Err(ScriptErrorKind::UnknownCommand(command.to_owned())),
+        }
+    }
+}
+
+/// Splits off the first whitespace separated token of a string, returning it and the trimmed remainder.
+fn split_token(s: &str) -> (&str, &str) {
+    let s = s.trim();
+
+    match s.split_once(char::is_whitespace) {
+        Some((token, rest)) => (token, rest.trim()),
+        None => (s, ""),
+    }
+}
+
+/// Checks a command's CVar path argument.
+fn validate_path(path: &str, command: &'static str) -> Result<(), ScriptErrorKind> {
+    if path.is_empty() {
+        return Err(ScriptErrorKind::MissingArgument(command));
+    }
+
+    crate::path::validate_cvar_path(path).map_err(|_| ScriptErrorKind::InvalidPath(path.to_owned()))
+}
+
+/// Parses the arguments of a `set` command. Values that aren't valid TOML are taken as a string, i.e. `Fxaa`.
+fn parse_set(path: &str, value: &str) -> Result<ScriptCommand, ScriptErrorKind> {
+    validate_path(path, "set")?;
+
+    if value.is_empty() {
+        return Err(ScriptErrorKind::MissingArgument("set"));
+    }
+
+    let value = Value::from_str(value).unwrap_or_else(|_| Value::from(value));
+
+    Ok(ScriptCommand::Set {
+        path: path.to_owned(),
+        value,
+    })
+}
+
+/// Strips a trailing `//` comment from a line, ignoring any within quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = None;
+
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '/') if previous == Some('/') => return &line[..i - 1],
+            _ => {}
+        }
+
+        previous = Some(c);
+    }
+
+    line
+}
+
+/// A parsed `.cfg` script, see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct CVarScript {
+    source: String,
+    commands: Vec<(usize, ScriptCommand)>,
+}
+
+impl CVarScript {
+    /// Parses a script. Scripts with any invalid line are rejected as a whole.
+    pub fn parse(text: &str, source: impl Into<String>) -> Result<Self, ScriptError> {
+        let source = source.into();
+        let mut commands = vec![];
+
+        for (i, line) in text.lines().enumerate() {
+            let line = strip_comment(line).trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            match line.parse() {
+                Ok(command) => commands.push((i + 1, command)),
+                Err(kind) => {
+                    return Err(ScriptError {
+                        source,
+                        line: i + 1,
+                        kind,
+                    });
+                }
+            }
+        }
+
+        Ok(Self { source, commands })
+    }
+
+    /// Reads and parses a script from the OS filesystem.
+    #[cfg(feature = "config_loader_fs")]
+    pub fn load_file(path: impl AsRef<std::path::Path>) -> Result<Self, ScriptError> {
+        let path = path.as_ref();
+        let source = path.to_string_lossy().into_owned();
+
+        let text = std::fs::read_to_string(path).map_err(|e| ScriptError {
+            source: source.clone(),
+            line: 0,
+            kind: ScriptErrorKind::ReadFailed(e.to_string()),
+        })?;
+
+        Self::parse(&text, source)
+    }
+
+    /// Returns the source of the script.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns every command in the script along with its 1-based line, in order.
+    pub fn commands(&self) -> impl Iterator<Item = (usize, &ScriptCommand)> {
+        self.commands.iter().map(|(line, command)| (*line, command))
+    }
+
+    /// Runs the script into a new layer of the given kind, to be added with [WorldExtensions::add_cvar_layer](crate::WorldExtensions::add_cvar_layer).
+    ///
+    /// Commands that fail (i.e. setting an unknown CVar, or a value it rejects) are logged and skipped, and don't stop the rest from running.
+    /// The layer is returned along with the first error encountered, if any.
+    pub fn to_layer(&self, world: &World, kind: LayerKind) -> (ConfigLayer, Result<(), CVarError>) {
+        let mut layer = ConfigLayer::new(kind, self.source.clone());
+
+        let res = run_script(world, self, &mut layer);
+
+        (layer, res)
+    }
+}
+
+/// Runs a script against a layer, returning the first error encountered. Failing commands are logged, and don't stop the rest from running.
+pub(crate) fn run_script(
+    world: &World,
+    script: &CVarScript,
+    layer: &mut ConfigLayer,
+) -> Result<(), CVarError> {
+    let mut errors = vec![];
+
+    run_inner(world, script, layer, &mut vec![], &mut errors);
+
+    errors.into_iter().next().map_or(Ok(()), Err)
+}
+
+fn run_inner(
+    world: &World,
+    script: &CVarScript,
+    layer: &mut ConfigLayer,
+    stack: &mut Vec<String>,
+    errors: &mut Vec<CVarError>,
+) {
+    stack.push(script.source.clone());
+
+    // Values from scripts run with `exec` don't point into the layer's source.
+    let top_level = script.source == layer.source();
+    let location = |line| top_level.then_some(SourceLocation { line, column: 1 });
+
+    for (line, command) in script.commands() {
+        let res = match command {
+            ScriptCommand::Set { path, value } => {
+                set(world, layer, path, value.clone(), location(line))
+            }
+            ScriptCommand::Reset(path) => default_value(world, path)
+                .and_then(|value| set(world, layer, path, value, location(line))),
+            ScriptCommand::Exec(path) => exec(world, script, line, path, stack)
+                .map(|nested| run_inner(world, &nested, layer, stack, errors)),
+        };
+
+        if let Err(e) = res {
+            warn!("{}:{line}: {e}", script.source);
+            errors.push(e);
+        }
+    }
+
+    stack.pop();
+}
+
+/// Checks a value against a CVar, then sets it on the layer.
+fn set(
+    world: &World,
+    layer: &mut ConfigLayer,
+    path: &str,
+    value: Value,
+    location: Option<SourceLocation>,
+) -> Result<(), CVarError> {
+    let management = world.resource::<CVarManagement>();
+    let path = canonical_path(management, path).ok_or(CVarError::UnknownCVar)?;
+
+    management.check_cvar_deserialize(world, path, value.clone().into_deserializer())?;
+
+    match location {
+        Some(location) => layer.insert_with_location(path, value, location),
+        None => layer.insert(path, value),
+    }
+
+    Ok(())
+}
+
+/// Returns the default value of a CVar, as a TOML value.
+fn default_value(world: &World, path: &str) -> Result<Value, CVarError> {
+    let management = world.resource::<CVarManagement>();
+    let cid = management.tree.get(path).ok_or(CVarError::UnknownCVar)?;
+    let registration = management
+        .resources
+        .get(&cid)
+        .ok_or(CVarError::MissingCid)?;
+
+    let default = registration
+        .data::<ReflectDefault>()
+        .ok_or(CVarError::BadCVarType)?
+        .default();
+    let inner = registration
+        .data::<ReflectCVar>()
+        .ok_or(CVarError::BadCVarType)?
+        .reflect_inner(default.as_partial_reflect())?;
+
+    serialize_value(&world.resource::<AppTypeRegistry>().read(), inner)
+}
+
+/// Loads a script run with `exec`, relative to the script running it.
+/// Scripts run from the console are relative to the [ScriptDirectory], if any.
+fn exec(
+    world: &World,
+    parent: &CVarScript,
+    line: usize,
+    path: &str,
+    stack: &[String],
+) -> Result<CVarScript, CVarError> {
+    let error = |kind| {
+        CVarError::ScriptError(ScriptError {
+            source: parent.source.clone(),
+            line,
+            kind,
+        })
+    };
+
+    #[cfg(feature = "config_loader_fs")]
+    {
+        let directory = if parent.source == CONSOLE_SOURCE {
+            world
+                .get_resource::<ScriptDirectory>()
+                .map(|x| x.0.clone())
+                .unwrap_or_default()
+        } else {
+            std::path::Path::new(&parent.source)
+                .parent()
+                .map(|x| x.to_owned())
+                .unwrap_or_default()
+        };
+
+        let path = directory.join(path);
+        let source = path.to_string_lossy().into_owned();
+
+        if stack.contains(&source) {
+            let cycle = stack
+                .iter()
+                .skip_while(|x| **x != source)
+                .chain(std::iter::once(&source))
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" -> ");
+
+            return Err(error(ScriptErrorKind::ExecCycle(cycle)));
+        }
+
+        CVarScript::load_file(&path).map_err(|e| match e.line {
+            0 => error(e.kind),
+            _ => CVarError::ScriptError(e),
+        })
+    }
+
+    #[cfg(not(feature = "config_loader_fs"))]
+    {
+        let _ = (world, path, stack);
+
+        Err(error(ScriptErrorKind::ReadFailed(
+            "exec requires the config_loader_fs feature".to_owned(),
+        )))
+    }
+}
+
+/// The directory scripts run from the console with `exec` are relative to, and where [AUTOEXEC] is looked for.
+#[cfg(feature = "config_loader_fs")]
+#[derive(bevy_ecs::resource::Resource, Debug, Clone)]
+pub struct ScriptDirectory(pub std::path::PathBuf);
+
+/// Runs a script against the console's [LayerKind::Runtime] layer, creating it if needed.
+pub(crate) fn run_console_script(world: &mut World, script: &CVarScript) -> Result<(), CVarError> {
+    let existing = world
+        .resource::<CVarManagement>()
+        .layers()
+        .iter()
+        .find(|(_, x)| x.kind() == LayerKind::Runtime && x.source() == CONSOLE_SOURCE)
+        .map(|(id, x)| (id, x.clone()));
+
+    let (id, mut layer) = match existing {
+        Some((id, layer)) => (Some(id), layer),
+        None => (None, ConfigLayer::new(LayerKind::Runtime, CONSOLE_SOURCE)),
+    };
+
+    let res = run_script(world, script, &mut layer);
+
+    match id {
+        Some(id) => {
+            crate::layers::replace_layer(world, id, layer);
+        }
+        None => {
+            crate::layers::add_layer(world, layer);
+        }
+    }
+
+    res
+}
@@ -0,0 +1,192 @@
+use std::error::Error;
+
+use crate::{
+    CVarError, WorldExtensions,
+    layers::{ConfigLayer, LayerKind, SourceLocation},
+    tests::{TestBool, TestInteger, TestVolume, make_test_app},
+};
+
+use super::{CVarScript, ScriptCommand, ScriptError, ScriptErrorKind};
+
+#[test]
+pub fn parse_script() -> Result<(), Box<dyn Error>> {
+    let script = CVarScript::parse(
+        "// A comment.\nset render.aa.method Fxaa\n\nrender.ssao.enabled false // Trailing comment.\nreset a.b\nexec \"other.cfg\"\nset a.name \"not // a comment\"\n",
+        "test.cfg",
+    )?;
+
+    let commands: Vec<String> = script
+        .commands()
+        .map(|(line, command)| match command {
+            ScriptCommand::Set { path, value } => format!("{line}: set {path} {value}"),
+            ScriptCommand::Reset(path) => format!("{line}: reset {path}"),
+            ScriptCommand::Exec(path) => format!("{line}: exec {path}"),
+        })
+        .collect();
+
+    assert_eq!(
+        commands,
+        [
+            "2: set render.aa.method \"Fxaa\"",
+            "4: set render.ssao.enabled false",
+            "5: reset a.b",
+            "6: exec other.cfg",
+            "7: set a.name \"not // a comment\"",
+        ]
+    );
+
+    let res = CVarScript::parse("set a.b 1\nbind w +forward\n", "test.cfg");
+
+    assert_eq!(
+        res.unwrap_err(),
+        ScriptError {
+            source: "test.cfg".to_owned(),
+            line: 2,
+            kind: ScriptErrorKind::UnknownCommand("bind".to_owned()),
+        }
+    );
+    assert!(matches!(
+        CVarScript::parse("set a.b", "test.cfg").map_err(|e| e.kind),
+        Err(ScriptErrorKind::MissingArgument("set"))
+    ));
+
+    Ok(())
+}
+
+#[test]
+pub fn script_layer() -> Result<(), Box<dyn Error>> {
+    let mut app = make_test_app();
+    let world = app.world_mut();
+
+    let default = **world.resource::<TestInteger>();
+
+    let mut user = ConfigLayer::new(LayerKind::User, "user.toml");
+    user.insert("testrig.test_int", 5.into());
+    world.add_cvar_layer(user);
+
+    let script = CVarScript::parse(
+        "testrig.test_bool false\nset testrig.old_volume 0.25\nreset testrig.test_int\nset testrig.missing 1\n",
+        "startup.cfg",
+    )?;
+
+    let (layer, res) = script.to_layer(world, LayerKind::Script);
+
+    // Failing commands are skipped, and reported.
+    assert!(matches!(res, Err(CVarError::UnknownCVar)));
+    assert!(layer.get("testrig.missing").is_none());
+    assert_eq!(
+        layer.location("testrig.derived.volume"),
+        Some(SourceLocation { line: 2, column: 1 })
+    );
+
+    world.add_cvar_layer(layer);
+
+    // Resetting a CVar masks the layers beneath the script.
+    assert_eq!(**world.resource::<TestInteger>(), default);
+    assert!(!**world.resource::<TestBool>());
+    assert_eq!(**world.resource::<TestVolume>(), 0.25);
+
+    Ok(())
+}
+
+#[test]
+pub fn console_commands() -> Result<(), Box<dyn Error>> {
+    let mut app = make_test_app();
+    let world = app.world_mut();
+
+    world.exec_cvar_command("set testrig.test_int 12 // From the console.")?;
+    world.exec_cvar_command("testrig.test_bool false")?;
+
+    assert_eq!(**world.resource::<TestInteger>(), 12);
+    assert!(!**world.resource::<TestBool>());
+
+    // Every command shares the same layer.
+    let console = world
+        .resource::<crate::CVarManagement>()
+        .layers()
+        .iter()
+        .filter(|(_, x)| x.kind() == LayerKind::Runtime)
+        .count();
+    assert_eq!(console, 1);
+
+    assert!(matches!(
+        world.exec_cvar_command("set testrig.derived.volume 2.0\nset testrig.test_int 13"),
+        Err(CVarError::ConstraintViolation(_))
+    ));
+    assert!(matches!(
+        world.exec_cvar_command("set testrig.missing 1"),
+        Err(CVarError::UnknownCVar)
+    ));
+    assert!(matches!(
+        world.exec_cvar_command("bind w +forward"),
+        Err(CVarError::ScriptError(_))
+    ));
+
+    // The remaining commands still run.
+    assert_eq!(**world.resource::<TestInteger>(), 13);
+    assert_eq!(**world.resource::<TestVolume>(), 0.5);
+
+    // Aliases are stored under the CVar's path, so explaining the CVar agrees with its value.
+    world.exec_cvar_command("set testrig.old_volume 0.25")?;
+
+    let explained = world
+        .resource::<crate::CVarManagement>()
+        .explain_cvar("testrig.derived.volume")?;
+
+    assert_eq!(**world.resource::<TestVolume>(), 0.25);
+    assert_eq!(explained.len(), 1);
+    assert_eq!(explained[0].value.to_string(), "0.25");
+
+    Ok(())
+}
+
+#[cfg(feature = "config_loader_fs")]
+#[test]
+pub fn exec_scripts() -> Result<(), Box<dyn Error>> {
+    use super::{AUTOEXEC, ScriptDirectory};
+    use crate::loader::CVarLoaderPluginBuilder;
+
+    let mut app = make_test_app();
+
+    app.add_plugins(
+        CVarLoaderPluginBuilder::default()
+            .with_script_directory("src/script/test_scripts".into())
+            .build(),
+    );
+    app.finish();
+
+    let world = app.world_mut();
+
+    assert_eq!(
+        world.resource::<ScriptDirectory>().0.join(AUTOEXEC),
+        std::path::Path::new("src/script/test_scripts/autoexec.cfg")
+    );
+
+    // Later commands win over those run before them with `exec`.
+    assert_eq!(**world.resource::<TestInteger>(), 3);
+    assert!(**world.resource::<TestBool>());
+    assert_eq!(**world.resource::<TestVolume>(), 0.75);
+
+    let res = world.exec_cvar_command("exec cycle_a.cfg");
+
+    let Err(CVarError::ScriptError(e)) = res else {
+        panic!("Expected an exec cycle, got {res:?}");
+    };
+    assert_eq!(
+        e.kind,
+        ScriptErrorKind::ExecCycle(
+            [
+                "src/script/test_scripts/cycle_a.cfg",
+                "src/script/test_scripts/cycle_b.cfg",
+                "src/script/test_scripts/cycle_a.cfg"
+            ]
+            .join(" -> ")
+        )
+    );
+
+    world.exec_cvar_command("exec graphics/high.cfg")?;
+
+    assert_eq!(**world.resource::<TestInteger>(), 2);
+
+    Ok(())
+}
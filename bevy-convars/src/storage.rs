@@ -0,0 +1,154 @@
+//! Provides the storage backends the user's config is loaded from and saved to.
+//!
+//! By default the user's config lives in a file on the OS filesystem ([FileStorage]), but platforms without one
+//! (i.e. browser local storage, or a console's save data API) can provide their own [CVarStorage].
+//! [MemoryStorage] keeps the config in memory, which is mostly useful for tests.
+//!
+//! With the `storage_sqlite` feature, `SqliteStorage` keeps one row per CVar in a SQLite database, along with when and where
+//! each value was set, and only writes the CVars that changed when saving.
+//!
+//! The storage is set with [CVarLoaderPluginBuilder::with_user_config_storage](crate::loader::CVarLoaderPluginBuilder::with_user_config_storage),
+//! after which the world's CVars can be saved to it with [WorldExtensions::save_user_config](crate::WorldExtensions::save_user_config),
+//! or automatically with [CVarLoaderPluginBuilder::with_auto_save](crate::loader::CVarLoaderPluginBuilder::with_auto_save).
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use bevy_ecs::{resource::Resource, world::World};
+use toml_edit::DocumentMut;
+
+use crate::{
+    CVarError,
+    format::{ConfigFormat, TomlFormat},
+    loader::{ConfigLoaderError, DocumentContext},
+    save::CVarSaveContext,
+};
+
+mod auto_save;
+#[cfg(feature = "config_loader_fs")]
+mod file;
+#[cfg(feature = "storage_sqlite")]
+mod sqlite;
+#[cfg(test)]
+mod tests;
+
+pub(crate) use auto_save::{auto_save_user_config, start_auto_save};
+#[cfg(feature = "config_loader_fs")]
+pub use file::FileStorage;
+#[cfg(feature = "storage_sqlite")]
+pub use sqlite::*;
+
+/// A place the user's config can be loaded from and saved to.
+///
+/// # Remarks
+/// Errors are reported as [std::io::Error], backends that aren't backed by IO can use [std::io::Error::other].
+pub trait CVarStorage: Send + Sync + 'static {
+    /// Returns a name for the storage, used as the source of the config loaded from it (see [DocumentContext::source]).
+    fn source(&self) -> String;
+
+    /// Loads the stored config, returning `None` if nothing has been stored yet.
+    fn load(&self) -> std::io::Result<Option<String>>;
+
+    /// Stores the given config, replacing the previous one.
+    fn save(&self, contents: &str) -> std::io::Result<()>;
+
+    /// Loads the stored config as TOML, along with any configs it includes, returning them in the order they should be applied.
+    /// # Remarks
+    /// Includes aren't supported by default, as they require a way to resolve paths relative to the config.
+    fn load_config(&self) -> Result<Vec<DocumentContext<String>>, ConfigLoaderError> {
+        let Some(text) = self.load()? else {
+            return Ok(vec![]);
+        };
+
+        Ok(vec![DocumentContext::parse::<TomlFormat>(
+            text,
+            self.source(),
+        )?])
+    }
+
+    /// Saves the world's CVars over the stored config, keeping its comments and layout. See [CVarSaveContext::save_world].
+    /// The world's [UserConfigSaveOptions] (if any) are applied to the context.
+    fn save_world(&self, world: &World) -> Result<(), CVarError> {
+        let document = match self.load()? {
+            Some(text) => text.parse::<DocumentMut>()?,
+            None => DocumentMut::new(),
+        };
+
+        let options = world
+            .get_resource::<UserConfigSaveOptions>()
+            .copied()
+            .unwrap_or_default();
+
+        let mut context = options.apply(CVarSaveContext::from_document(document));
+        context.save_world(world)?;
+
+        Ok(self.save(&context.to_string())?)
+    }
+}
+
+/// The storage the user's config is loaded from, inserted by the [CVarLoaderPlugin](crate::loader::CVarLoaderPlugin) if one was set.
+#[derive(Resource, Clone)]
+pub struct UserConfigStorage(pub Arc<dyn CVarStorage>);
+
+/// How the user's config is saved by [CVarStorage::save_world], inserted by the [CVarLoaderPlugin](crate::loader::CVarLoaderPlugin).
+/// Set with [CVarLoaderPluginBuilder::with_save_options](crate::loader::CVarLoaderPluginBuilder::with_save_options).
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UserConfigSaveOptions {
+    /// Whether entries of CVars at their default value are removed, see [CVarSaveContext::with_remove_defaults].
+    pub remove_defaults: bool,
+    /// Whether each saved CVar is commented with its description and metadata, see [CVarSaveContext::with_comments].
+    pub comments: bool,
+}
+
+impl UserConfigSaveOptions {
+    /// Applies the options to the given context.
+    pub fn apply<F: ConfigFormat>(self, context: CVarSaveContext<F>) -> CVarSaveContext<F> {
+        context
+            .with_remove_defaults(self.remove_defaults)
+            .with_comments(self.comments)
+    }
+}
+
+/// Stores the config in memory. Clones share the same contents, so one can be handed to the
+/// [CVarLoaderPlugin](crate::loader::CVarLoaderPlugin) while another is used to inspect what was saved.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    contents: Arc<Mutex<Option<String>>>,
+}
+
+impl MemoryStorage {
+    /// Creates an empty storage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a storage already containing the given config.
+    pub fn with_contents(contents: impl Into<String>) -> Self {
+        Self {
+            contents: Arc::new(Mutex::new(Some(contents.into()))),
+        }
+    }
+
+    /// Returns the stored config, if any.
+    pub fn contents(&self) -> Option<String> {
+        self.contents
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl CVarStorage for MemoryStorage {
+    fn source(&self) -> String {
+        "memory".to_owned()
+    }
+
+    fn load(&self) -> std::io::Result<Option<String>> {
+        Ok(self.contents())
+    }
+
+    fn save(&self, contents: &str) -> std::io::Result<()> {
+        *self.contents.lock().unwrap_or_else(PoisonError::into_inner) = Some(contents.to_owned());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use bevy_app::AppExit;
+use bevy_ecs::{
+    component::Tick,
+    event::{EventCursor, Events},
+    resource::Resource,
+    system::Local,
+    world::World,
+};
+use bevy_log::warn;
+use bevy_platform::time::Instant;
+
+use crate::{CVarFlags, CVarManagement, reflect::ReflectCVar};
+
+use super::UserConfigStorage;
+
+/// Tracks unsaved changes to [SAVED](CVarFlags::SAVED) CVars, for [auto_save_user_config].
+#[derive(Resource)]
+struct UserConfigAutoSave {
+    /// How long to wait after the last change before saving.
+    debounce: Duration,
+    /// When the last unsaved change was seen, if there are any.
+    changed_at: Option<Instant>,
+    /// The change tick CVars were last checked at.
+    last_check: Tick,
+}
+
+/// Starts watching for changes to save to the user's config. Changes made up to this point (i.e. loading the user's config) aren't saved.
+pub(crate) fn start_auto_save(world: &mut World, debounce: Duration) {
+    let last_check = world.increment_change_tick();
+
+    world.insert_resource(UserConfigAutoSave {
+        debounce,
+        changed_at: None,
+        last_check,
+    });
+}
+
+/// Returns whether any [SAVED](CVarFlags::SAVED) CVar changed between the given ticks.
+fn saved_cvars_changed(world: &World, since: Tick, this_run: Tick) -> bool {
+    let Some(management) = world.get_resource::<CVarManagement>() else {
+        return false;
+    };
+
+    management
+        .iterate_cvar_types()
+        .filter_map(|x| x.data::<ReflectCVar>())
+        .filter(|x| x.flags().contains(CVarFlags::SAVED))
+        .filter_map(|x| management.tree.get(x.cvar_path()))
+        .filter_map(|x| world.get_resource_change_ticks_by_id(x))
+        .any(|x| x.is_changed(since, this_run))
+}
+
+/// Saves the world's CVars to the [UserConfigStorage] once no [SAVED](CVarFlags::SAVED) CVar has changed for the debounce duration,
+/// or right away when the app is exiting.
+pub(crate) fn auto_save_user_config(
+    world: &mut World,
+    mut exit_events: Local<EventCursor<AppExit>>,
+) {
+    let Some(last_check) = world
+        .get_resource::<UserConfigAutoSave>()
+        .map(|x| x.last_check)
+    else {
+        return;
+    };
+
+    let now = Instant::now();
+    // Changes made from here on get a newer tick, and as such are seen next time.
+    let this_run = world.increment_change_tick();
+    let changed = saved_cvars_changed(world, last_check, this_run);
+
+    let exiting = world
+        .get_resource::<Events<AppExit>>()
+        .is_some_and(|x| exit_events.read(x).count() > 0);
+
+    let mut state = world.resource_mut::<UserConfigAutoSave>();
+
+    state.last_check = this_run;
+
+    if changed {
+        state.changed_at = Some(now);
+    }
+
+    let Some(changed_at) = state.changed_at else {
+        return;
+    };
+
+    if !exiting && now.duration_since(changed_at) < state.debounce {
+        return;
+    }
+
+    state.changed_at = None;
+
+    let Some(storage) = world.get_resource::<UserConfigStorage>() else {
+        return;
+    };
+
+    if let Err(e) = storage.0.save_world(world) {
+        warn!(
+            "Failed to save the user's config ({}), got error: {e}",
+            storage.0.source()
+        );
+    }
+}
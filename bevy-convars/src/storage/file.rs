@@ -0,0 +1,174 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use bevy_log::warn;
+use toml_edit::ImDocument;
+
+use crate::loader::{ConfigLoader, ConfigLoaderError, DocumentContext};
+
+use super::CVarStorage;
+
+/// Stores the config in a file on the OS filesystem.
+///
+/// Saving is atomic: the config is written to a temporary file next to it (`user.toml.tmp`), flushed to disk and then renamed into place,
+/// so the file is never left half-written. On Unix the directory is flushed after the rename too, making the save durable across a crash,
+/// while elsewhere a power loss right after saving may still leave the previous version in place.
+/// The previous versions of the file are kept as rotated backups (`user.toml.1.bak` being the newest), and if the file fails to parse
+/// when loaded, the newest backup that parses is loaded instead.
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    path: PathBuf,
+    /// How many previous versions of the file are kept.
+    backups: usize,
+}
+
+impl FileStorage {
+    /// How many backups are kept by default.
+    pub const DEFAULT_BACKUPS: usize = 3;
+
+    /// Creates a storage for the file at the given path. The file (and its directory) are created when first needed.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            backups: Self::DEFAULT_BACKUPS,
+        }
+    }
+
+    /// Sets how many previous versions of the file are kept as backups, [FileStorage::DEFAULT_BACKUPS] by default.
+    pub fn with_backups(self, backups: usize) -> Self {
+        Self { backups, ..self }
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path of the given backup, 1 being the newest.
+    pub fn backup_path(&self, index: usize) -> PathBuf {
+        self.sibling(&format!(".{index}.bak"))
+    }
+
+    /// Returns the path of the file with the given suffix appended to its name.
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut name = self
+            .path
+            .file_name()
+            .map(OsString::from)
+            .unwrap_or_default();
+        name.push(suffix);
+
+        self.path.with_file_name(name)
+    }
+
+    /// Returns the file to load, being the file itself unless it fails to parse, in which case it's the newest backup that parses.
+    /// Returns `None` if the file doesn't exist.
+    fn valid_file(&self) -> std::io::Result<Option<(PathBuf, String)>> {
+        let text = match std::fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let error = match ImDocument::parse(text.as_str()) {
+            Ok(_) => return Ok(Some((self.path.clone(), text))),
+            Err(error) => error,
+        };
+
+        for path in (1..=self.backups).map(|x| self.backup_path(x)) {
+            let Ok(backup) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+
+            if ImDocument::parse(backup.as_str()).is_ok() {
+                warn!(
+                    "The user's config ({}) failed to parse, falling back to the backup {}. Got error: {error}",
+                    self.path.display(),
+                    path.display()
+                );
+
+                return Ok(Some((path, backup)));
+            }
+        }
+
+        // Nothing to fall back to, so the error is reported as-is when loading.
+        Ok(Some((self.path.clone(), text)))
+    }
+}
+
+impl CVarStorage for FileStorage {
+    fn source(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+
+    /// Reads the file, or the newest backup that parses if it doesn't.
+    fn load(&self) -> std::io::Result<Option<String>> {
+        Ok(self.valid_file()?.map(|(_, text)| text))
+    }
+
+    /// Writes the file through a temporary file, rotating the backups if the previous version parses.
+    fn save(&self, contents: &str) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent().filter(|x| !x.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let temp = self.sibling(".tmp");
+
+        {
+            let mut file = File::create(&temp)?;
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()?;
+        }
+
+        // Corrupt versions aren't worth keeping, so they don't push out the valid backups.
+        let previous = std::fs::read_to_string(&self.path).ok();
+
+        if self.backups > 0
+            && previous
+                .as_deref()
+                .is_some_and(|x| x != contents && ImDocument::parse(x).is_ok())
+        {
+            for index in (1..self.backups).rev() {
+                let from = self.backup_path(index);
+
+                if from.exists() {
+                    std::fs::rename(from, self.backup_path(index + 1))?;
+                }
+            }
+
+            std::fs::copy(&self.path, self.backup_path(1))?;
+        }
+
+        std::fs::rename(temp, &self.path)?;
+
+        // The rename itself only survives a crash once the directory holding the file is flushed too.
+        #[cfg(unix)]
+        {
+            let parent = self
+                .path
+                .parent()
+                .filter(|x| !x.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+
+            File::open(parent)?.sync_all()?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads the file along with the configs it includes, see [ConfigLoader::load_file].
+    /// The file is created if it doesn't exist, and the newest backup that parses is loaded instead if it fails to parse.
+    fn load_config(&self) -> Result<Vec<DocumentContext<String>>, ConfigLoaderError> {
+        let Some((path, _)) = self.valid_file()? else {
+            self.save("")?;
+
+            return ConfigLoader::default().load_file(&self.path);
+        };
+
+        ConfigLoader::default().load_file(path)
+    }
+}
@@ -0,0 +1,342 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bevy_ecs::world::World;
+use bevy_platform::collections::{HashMap, HashSet};
+use rusqlite::{Connection, params};
+use toml_edit::{Item, Table, Value};
+
+use crate::{
+    CVarError, CVarManagement,
+    layers::same_value,
+    reflect::ReflectCVar,
+    save::{CVarSaveContext, SavedValue, saved_cvars},
+};
+
+use super::{CVarStorage, UserConfigSaveOptions};
+
+/// The provenance of values set without going through a config layer, i.e. by modifying the CVar's resource directly.
+pub const RUNTIME_PROVENANCE: &str = "runtime";
+
+/// The provenance of values written by [CVarStorage::save], as part of a whole document.
+pub const DOCUMENT_PROVENANCE: &str = "document";
+
+/// A row of a [SqliteStorage], holding the value of one CVar.
+#[derive(Debug, Clone)]
+pub struct StoredCVar {
+    /// The path of the CVar.
+    pub path: String,
+    /// The value of the CVar.
+    pub value: Value,
+    /// When the value was last written.
+    pub modified: SystemTime,
+    /// Where the value came from when it was written, either the source of the layer that set it
+    /// (see [LayerEntry::source](crate::layers::LayerEntry::source)), [RUNTIME_PROVENANCE] or [DOCUMENT_PROVENANCE].
+    pub provenance: String,
+}
+
+/// Stores the config in a SQLite database, as one row per CVar path.
+///
+/// Unlike other storages, [CVarStorage::save_world] only writes the CVars that changed since they were last saved,
+/// rather than rewriting the whole config.
+///
+/// # Remarks
+/// Keys are stored by their full path, so the comments and layout of a config saved through [CVarStorage::save] aren't kept.
+/// A table in such a config is stored as a single row if it's the value of a CVar, which is known for the CVars of any world
+/// saved with [CVarStorage::save_world] and for paths that already have a row. Other tables are stored as one row per key.
+///
+/// Clones share the same connection.
+#[derive(Debug, Clone)]
+pub struct SqliteStorage {
+    connection: Arc<Mutex<Connection>>,
+    source: String,
+    /// The paths of the CVars of the worlds saved so far, see [CVarStorage::save].
+    cvar_paths: Arc<Mutex<HashSet<String>>>,
+}
+
+impl SqliteStorage {
+    /// Opens (or creates) the database at the given path.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+
+        Self::new(
+            Connection::open(path).map_err(std::io::Error::other)?,
+            path.to_string_lossy().into_owned(),
+        )
+    }
+
+    /// Opens a database that only lives in memory.
+    pub fn open_in_memory() -> std::io::Result<Self> {
+        Self::new(
+            Connection::open_in_memory().map_err(std::io::Error::other)?,
+            ":memory:".to_owned(),
+        )
+    }
+
+    fn new(connection: Connection, source: String) -> std::io::Result<Self> {
+        connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS cvars (
+                    path TEXT PRIMARY KEY NOT NULL,
+                    value TEXT NOT NULL,
+                    modified INTEGER NOT NULL,
+                    provenance TEXT NOT NULL
+                )",
+                (),
+            )
+            .map_err(std::io::Error::other)?;
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            source,
+            cvar_paths: Default::default(),
+        })
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns every stored CVar, sorted by path.
+    pub fn entries(&self) -> std::io::Result<Vec<StoredCVar>> {
+        let connection = self.connection();
+
+        let mut statement = connection
+            .prepare("SELECT path, value, modified, provenance FROM cvars ORDER BY path")
+            .map_err(std::io::Error::other)?;
+
+        let rows = statement
+            .query_map((), |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })
+            .map_err(std::io::Error::other)?;
+
+        let mut entries = vec![];
+
+        for row in rows {
+            let (path, value, modified, provenance) = row.map_err(std::io::Error::other)?;
+
+            let value = value.parse::<Value>().map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Malformed value for {path}: {e}"),
+                )
+            })?;
+
+            entries.push(StoredCVar {
+                path,
+                value,
+                modified: UNIX_EPOCH + Duration::from_secs(modified.max(0) as u64),
+                provenance,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Writes the world's [SAVED](crate::CVarFlags::SAVED) CVars whose value differs from the stored one, returning how many rows changed.
+    /// Orphans that were purged (see [CVarManagement::purge_orphan]) are removed.
+    /// # Remarks
+    /// Like [CVarSaveContext::save_world], CVars that were never changed from their default value are skipped, so their rows (if any) are left untouched,
+    /// as are the rows for any other key (i.e. orphans). CVars changed back to their default value are written, so their rows don't keep the old value.
+    /// With [UserConfigSaveOptions::remove_defaults], the rows of both are removed instead.
+    pub fn save_changed(&self, world: &World) -> Result<usize, CVarError> {
+        let management = world.resource::<CVarManagement>();
+
+        self.cvar_paths
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(
+                management
+                    .iterate_cvar_types()
+                    .filter_map(|x| x.data::<ReflectCVar>())
+                    .map(|x| x.cvar_path().to_owned()),
+            );
+
+        let stored: HashMap<String, Value> = self
+            .entries()?
+            .into_iter()
+            .map(|x| (x.path, x.value))
+            .collect();
+
+        let now = now();
+        let mut connection = self.connection();
+        let transaction = connection.transaction().map_err(std::io::Error::other)?;
+        let mut changed = 0;
+
+        let remove = |path: &str| -> Result<usize, CVarError> {
+            // Also removes rows for the fields of a table written by a whole document, i.e. `a.b.name` for `a.b`.
+            Ok(transaction
+                .execute(
+                    "DELETE FROM cvars WHERE path = ?1 OR substr(path, 1, length(?1) + 1) = ?1 || '.'",
+                    params![path],
+                )
+                .map_err(std::io::Error::other)?)
+        };
+
+        for path in management.purged_orphans.iter() {
+            changed += remove(path)?;
+        }
+
+        let remove_defaults = world
+            .get_resource::<UserConfigSaveOptions>()
+            .is_some_and(|x| x.remove_defaults);
+
+        for cvar in saved_cvars(world)? {
+            let path = cvar.cvar.cvar_path();
+
+            let value = match cvar.value {
+                SavedValue::Untouched | SavedValue::Default(_) if remove_defaults => {
+                    changed += remove(path)?;
+                    continue;
+                }
+                SavedValue::Untouched => continue,
+                SavedValue::Default(value) | SavedValue::Changed(value) => value,
+            };
+
+            if stored.get(path).is_some_and(|x| same_value(x, &value)) {
+                continue;
+            }
+
+            let provenance = management
+                .explain_cvar(path)?
+                .into_iter()
+                .find(|x| x.effective && same_value(&x.value, &value))
+                .map_or_else(|| RUNTIME_PROVENANCE.to_owned(), |x| x.source);
+
+            remove(path)?;
+            transaction
+                .execute(
+                    "INSERT INTO cvars (path, value, modified, provenance) VALUES (?1, ?2, ?3, ?4)",
+                    params![path, value_text(&value), now, provenance],
+                )
+                .map_err(std::io::Error::other)?;
+            changed += 1;
+        }
+
+        transaction.commit().map_err(std::io::Error::other)?;
+
+        Ok(changed)
+    }
+}
+
+impl CVarStorage for SqliteStorage {
+    fn source(&self) -> String {
+        self.source.clone()
+    }
+
+    /// Builds a TOML config from the stored CVars, returning `None` if there are none.
+    fn load(&self) -> std::io::Result<Option<String>> {
+        let entries = self.entries()?;
+
+        if entries.is_empty() {
+            return Ok(None);
+        }
+
+        let mut context = CVarSaveContext::blank();
+
+        for entry in entries {
+            context
+                .save_value(&entry.path, entry.value)
+                .map_err(std::io::Error::other)?;
+        }
+
+        Ok(Some(context.to_string()))
+    }
+
+    /// Replaces every stored CVar with the keys of the given TOML config, recorded with [DOCUMENT_PROVENANCE].
+    /// Tables holding the value of a known CVar are stored as a single row, see [SqliteStorage].
+    fn save(&self, contents: &str) -> std::io::Result<()> {
+        /// Collects the values of a table by their full path, stopping at the tables of CVars.
+        fn flatten(
+            prefix: &str,
+            table: &Table,
+            cvars: &HashSet<String>,
+            values: &mut Vec<(String, Value)>,
+        ) {
+            for (key, item) in table.iter() {
+                let path = if prefix.is_empty() {
+                    key.to_owned()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+
+                match item {
+                    Item::Table(table) if cvars.contains(&path) => {
+                        values.push((path, Value::InlineTable(table.clone().into_inline_table())));
+                    }
+                    Item::Table(table) => flatten(&path, table, cvars, values),
+                    Item::ArrayOfTables(array) => {
+                        values.push((path, Value::Array(array.clone().into_array())));
+                    }
+                    Item::Value(value) => values.push((path, value.clone())),
+                    Item::None => {}
+                }
+            }
+        }
+
+        let document = contents
+            .parse::<toml_edit::DocumentMut>()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+        let mut cvars = self
+            .cvar_paths
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        cvars.extend(self.entries()?.into_iter().map(|x| x.path));
+
+        let mut values = vec![];
+        flatten("", document.as_table(), &cvars, &mut values);
+
+        let now = now();
+        let mut connection = self.connection();
+        let transaction = connection.transaction().map_err(std::io::Error::other)?;
+
+        transaction
+            .execute("DELETE FROM cvars", ())
+            .map_err(std::io::Error::other)?;
+
+        for (path, value) in values {
+            transaction
+                .execute(
+                    "INSERT INTO cvars (path, value, modified, provenance) VALUES (?1, ?2, ?3, ?4)",
+                    params![path, value_text(&value), now, DOCUMENT_PROVENANCE],
+                )
+                .map_err(std::io::Error::other)?;
+        }
+
+        transaction.commit().map_err(std::io::Error::other)
+    }
+
+    /// Writes only the CVars that changed, see [SqliteStorage::save_changed].
+    fn save_world(&self, world: &World) -> Result<(), CVarError> {
+        self.save_changed(world).map(|_| ())
+    }
+}
+
+/// Returns the current time, as seconds since the unix epoch.
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+/// Returns the text of a value, without its surrounding whitespace and comments.
+fn value_text(value: &Value) -> String {
+    let mut value = value.clone();
+    value.decor_mut().clear();
+
+    value.to_string()
+}
@@ -0,0 +1,400 @@
+use std::{error::Error, time::Duration};
+
+use bevy_app::{App, AppExit};
+
+use crate::{
+    CVarsPlugin, WorldExtensions,
+    loader::CVarLoaderPluginBuilder,
+    tests::{TestBool, TestCVarsPlugin, TestInteger},
+};
+
+#[cfg(any(feature = "config_loader_fs", feature = "storage_sqlite"))]
+use super::CVarStorage;
+use super::{MemoryStorage, UserConfigSaveOptions};
+
+#[test]
+pub fn load_and_save_memory_storage() -> Result<(), Box<dyn Error>> {
+    let storage = MemoryStorage::with_contents("# Kept when saving.\n[testrig]\ntest_int = 4\n");
+
+    let mut app = App::new();
+
+    app.add_plugins((
+        CVarLoaderPluginBuilder::default()
+            .with_user_config_storage(storage.clone())
+            .build(),
+        CVarsPlugin,
+        TestCVarsPlugin,
+    ));
+
+    app.finish();
+
+    let world = app.world_mut();
+
+    assert_eq!(**world.resource::<TestInteger>(), 4);
+
+    **world.resource_mut::<TestBool>() = false;
+
+    assert!(world.save_user_config()?);
+
+    assert_eq!(
+        storage.contents().as_deref(),
+        Some("# Kept when saving.\n[testrig]\ntest_int = 4\ntest_bool = false\n")
+    );
+
+    // Without a storage, there's nothing to save to.
+    let mut app = App::new();
+    app.add_plugins((CVarLoaderPluginBuilder::default().build(), CVarsPlugin));
+    app.finish();
+
+    assert!(!app.world_mut().save_user_config()?);
+
+    Ok(())
+}
+
+#[test]
+pub fn save_options() -> Result<(), Box<dyn Error>> {
+    let storage = MemoryStorage::with_contents("[testrig]\ntest_int = 4\ntest_bool = false\n");
+
+    let mut app = App::new();
+
+    app.add_plugins((
+        CVarLoaderPluginBuilder::default()
+            .with_user_config_storage(storage.clone())
+            .with_save_options(UserConfigSaveOptions {
+                remove_defaults: true,
+                ..Default::default()
+            })
+            .build(),
+        CVarsPlugin,
+        TestCVarsPlugin,
+    ));
+
+    app.finish();
+
+    let world = app.world_mut();
+
+    assert!(!**world.resource::<TestBool>());
+
+    // Reset to its default, so its entry is removed.
+    **world.resource_mut::<TestBool>() = true;
+
+    assert!(world.save_user_config()?);
+
+    assert_eq!(
+        storage.contents().as_deref(),
+        Some("[testrig]\ntest_int = 4\n")
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn auto_save_user_config() -> Result<(), Box<dyn Error>> {
+    let make_app = |storage: &MemoryStorage, debounce: Duration| {
+        let mut app = App::new();
+
+        app.add_plugins((
+            CVarLoaderPluginBuilder::default()
+                .with_user_config_storage(storage.clone())
+                .with_auto_save(debounce)
+                .build(),
+            CVarsPlugin,
+            TestCVarsPlugin,
+        ));
+
+        app.finish();
+        app
+    };
+
+    let storage = MemoryStorage::with_contents("# Kept when saving.\n[testrig]\ntest_int = 4\n");
+    let mut app = make_app(&storage, Duration::ZERO);
+
+    // Loading the user's config doesn't count as a change.
+    app.update();
+
+    assert_eq!(
+        storage.contents().as_deref(),
+        Some("# Kept when saving.\n[testrig]\ntest_int = 4\n")
+    );
+
+    **app.world_mut().resource_mut::<TestBool>() = false;
+    app.update();
+
+    assert_eq!(
+        storage.contents().as_deref(),
+        Some("# Kept when saving.\n[testrig]\ntest_int = 4\ntest_bool = false\n")
+    );
+
+    // Changes within the debounce are only saved when exiting.
+    let storage = MemoryStorage::new();
+    let mut app = make_app(&storage, Duration::from_secs(3600));
+
+    **app.world_mut().resource_mut::<TestInteger>() = 9;
+    app.update();
+
+    assert_eq!(storage.contents(), None);
+
+    app.world_mut().send_event(AppExit::Success);
+    app.update();
+
+    assert_eq!(
+        storage.contents().as_deref(),
+        Some("[testrig]\ntest_int = 9\n")
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "config_loader_asset")]
+pub fn auto_save_after_asset_layers() -> Result<(), Box<dyn Error>> {
+    use bevy_app::TaskPoolPlugin;
+    use bevy_asset::AssetPlugin;
+    use bevy_state::{app::StatesPlugin, state::State};
+    use toml_edit::ImDocument;
+
+    use crate::loader::{CVarLoadState, DocumentContext};
+
+    let layers = DocumentContext::new(
+        ImDocument::parse("[core]\nconfig_layers = [\"test_document.toml\"]\n".to_owned())?,
+        "layers.toml".to_owned(),
+    );
+    let storage = MemoryStorage::with_contents("# Kept when saving.\n[testrig]\ntest_int = 7\n");
+
+    let mut app = App::new();
+
+    app.add_plugins((
+        TaskPoolPlugin::default(),
+        AssetPlugin {
+            file_path: "src/loader".to_owned(),
+            ..Default::default()
+        },
+        StatesPlugin,
+        CVarsPlugin,
+        TestCVarsPlugin,
+        CVarLoaderPluginBuilder::default()
+            .add_layer(layers)
+            .with_asset_layers("")
+            .with_user_config_storage(storage.clone())
+            .with_auto_save(Duration::ZERO)
+            .build(),
+    ));
+
+    app.finish();
+    app.cleanup();
+
+    for _ in 0..100 {
+        app.update();
+
+        if *app.world().resource::<State<CVarLoadState>>().get() == CVarLoadState::Ready {
+            break;
+        }
+
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    app.update();
+
+    // Neither the asset layers nor the user's config count as a change.
+    assert_eq!(**app.world().resource::<TestInteger>(), 7);
+    assert!(!**app.world().resource::<TestBool>());
+    assert_eq!(
+        storage.contents().as_deref(),
+        Some("# Kept when saving.\n[testrig]\ntest_int = 7\n")
+    );
+
+    // Changes made once the layers are applied are saved as usual.
+    **app.world_mut().resource_mut::<TestInteger>() = 9;
+    app.update();
+
+    assert!(
+        storage
+            .contents()
+            .is_some_and(|x| x.contains("test_int = 9\n"))
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "config_loader_fs")]
+pub fn file_storage() -> Result<(), Box<dyn Error>> {
+    use super::FileStorage;
+
+    let directory =
+        std::env::temp_dir().join(format!("bevy-convars-storage-{}", std::process::id()));
+    let storage = FileStorage::new(directory.join("nested/user.toml"));
+
+    assert_eq!(storage.load()?, None);
+
+    // Loading the config creates the file, along with its directory.
+    let documents = storage.load_config()?;
+
+    assert_eq!(documents.len(), 1);
+    assert_eq!(storage.load()?.as_deref(), Some(""));
+
+    storage.save("[testrig]\ntest_int = 4\n")?;
+
+    assert_eq!(
+        storage.load()?.as_deref(),
+        Some("[testrig]\ntest_int = 4\n")
+    );
+
+    std::fs::remove_dir_all(directory)?;
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "storage_sqlite")]
+pub fn sqlite_storage() -> Result<(), Box<dyn Error>> {
+    use super::{DOCUMENT_PROVENANCE, SqliteStorage};
+
+    let storage = SqliteStorage::open_in_memory()?;
+
+    storage.save("[testrig]\ntest_int = 4\n")?;
+
+    let mut app = App::new();
+
+    app.add_plugins((
+        CVarLoaderPluginBuilder::default()
+            .with_user_config_storage(storage.clone())
+            .build(),
+        CVarsPlugin,
+        TestCVarsPlugin,
+    ));
+
+    app.finish();
+
+    let world = app.world_mut();
+
+    assert_eq!(**world.resource::<TestInteger>(), 4);
+
+    world.exec_cvar_command("set testrig.test_bool false")?;
+
+    assert!(world.save_user_config()?);
+
+    let entries: Vec<(String, String, String)> = storage
+        .entries()?
+        .into_iter()
+        .map(|x| (x.path, x.value.to_string(), x.provenance))
+        .collect();
+
+    // The unchanged value keeps its row as-is.
+    assert_eq!(
+        entries,
+        [
+            (
+                "testrig.test_bool".to_owned(),
+                "false".to_owned(),
+                "console".to_owned()
+            ),
+            (
+                "testrig.test_int".to_owned(),
+                "4".to_owned(),
+                DOCUMENT_PROVENANCE.to_owned()
+            ),
+        ]
+    );
+
+    assert_eq!(storage.save_changed(world)?, 0);
+
+    world.exec_cvar_command("set testrig.test_int 9")?;
+
+    assert_eq!(storage.save_changed(world)?, 1);
+    assert_eq!(
+        storage.load()?.as_deref(),
+        Some("[testrig]\ntest_bool = false\ntest_int = 9\n")
+    );
+
+    // Struct CVars are stored as one row, whether saved from the world or as part of a whole document.
+    world.exec_cvar_command("set testrig.test_struct { name = \"a\", weight = 2 }")?;
+    storage.save_changed(world)?;
+
+    let paths = |storage: &SqliteStorage| -> std::io::Result<Vec<String>> {
+        Ok(storage.entries()?.into_iter().map(|x| x.path).collect())
+    };
+    let expected = [
+        "testrig.test_bool",
+        "testrig.test_int",
+        "testrig.test_struct",
+    ];
+
+    assert_eq!(paths(&storage)?, expected);
+
+    storage.save(&storage.load()?.unwrap_or_default())?;
+
+    assert_eq!(paths(&storage)?, expected);
+
+    // CVars changed back to their default value are written, rather than keeping their old row.
+    world.exec_cvar_command("set testrig.test_int -5")?;
+
+    assert_eq!(storage.save_changed(world)?, 1);
+    assert_eq!(storage.entries()?[1].value.as_integer(), Some(-5));
+
+    // Rows of CVars reset to their default are removed when set in the save options.
+    world.insert_resource(UserConfigSaveOptions {
+        remove_defaults: true,
+        ..Default::default()
+    });
+    world.exec_cvar_command("set testrig.test_bool true")?;
+
+    assert_eq!(storage.save_changed(world)?, 2);
+    assert_eq!(paths(&storage)?, ["testrig.test_struct"]);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "config_loader_fs")]
+pub fn file_storage_backups() -> Result<(), Box<dyn Error>> {
+    use super::FileStorage;
+
+    let directory =
+        std::env::temp_dir().join(format!("bevy-convars-backups-{}", std::process::id()));
+    let storage = FileStorage::new(directory.join("user.toml")).with_backups(2);
+
+    for value in 1..=3 {
+        storage.save(&format!("[testrig]\ntest_int = {value}\n"))?;
+    }
+
+    let read = |path: &std::path::Path| std::fs::read_to_string(path).unwrap();
+
+    assert_eq!(read(storage.path()), "[testrig]\ntest_int = 3\n");
+    assert_eq!(read(&storage.backup_path(1)), "[testrig]\ntest_int = 2\n");
+    assert_eq!(read(&storage.backup_path(2)), "[testrig]\ntest_int = 1\n");
+    assert!(!storage.backup_path(3).exists());
+    assert!(!directory.join("user.toml.tmp").exists());
+
+    // A corrupt file falls back to the newest backup, both when loading and when saving over it.
+    std::fs::write(storage.path(), "[testrig\ntest_int = ")?;
+
+    let mut app = App::new();
+
+    app.add_plugins((
+        CVarLoaderPluginBuilder::default()
+            .with_user_config_storage(storage.clone())
+            .build(),
+        CVarsPlugin,
+        TestCVarsPlugin,
+    ));
+
+    app.finish();
+
+    assert_eq!(**app.world().resource::<TestInteger>(), 2);
+
+    **app.world_mut().resource_mut::<TestBool>() = false;
+    app.world_mut().save_user_config()?;
+
+    assert_eq!(
+        read(storage.path()),
+        "[testrig]\ntest_int = 2\ntest_bool = false\n"
+    );
+    // The corrupt version isn't kept.
+    assert_eq!(read(&storage.backup_path(1)), "[testrig]\ntest_int = 2\n");
+    assert_eq!(read(&storage.backup_path(2)), "[testrig]\ntest_int = 1\n");
+
+    std::fs::remove_dir_all(directory)?;
+
+    Ok(())
+}
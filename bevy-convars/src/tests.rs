@@ -1,16 +1,52 @@
-use std::error::Error;
+use std::{collections::BTreeMap, error::Error, fmt::Display, str::FromStr};
 
 use bevy_app::App;
+use bevy_reflect::Reflect;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    CVarError, CVarFlags, CVarMeta, CVarsPlugin, cvar_collection,
+    AppExtensions, CVar, CVarError, CVarFlags, CVarMeta, CVarsPlugin, cvar_collection,
     defaults::{IsDefault, IsDefaultMut},
 };
 
 const TEST_INTEGER_INIT_VAL: i32 = -5;
 
+/// A struct-typed value, for testing CVars that are written as tables.
+#[derive(Reflect, Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct TestStructValue {
+    pub name: String,
+    pub weight: i32,
+}
+
+/// A value that only supports reflection, for testing CVars without serde support.
+#[derive(Reflect, Default, Debug, Clone, PartialEq)]
+pub struct TestReflectOnlyValue {
+    pub scale: f32,
+    pub label: String,
+}
+
+/// An opaque value that can only be converted to and from a string, for testing CVars without serde support.
+#[derive(Reflect, Debug, Clone, Copy, PartialEq)]
+#[reflect(opaque)]
+pub struct TestColorValue(pub u32);
+
+impl FromStr for TestColorValue {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u32::from_str_radix(s.trim_start_matches('#'), 16).map(Self)
+    }
+}
+
+impl Display for TestColorValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:06x}", self.0)
+    }
+}
+
 cvar_collection! {
     /// Collection of test CVars you can use as a system argument.
+    #[allow(dead_code, reason = "Only the plugin is used by tests.")]
     pub struct TestCVars & TestCVarsMut {
         /// Test boolean flag.
         test_bool = cvar TestBool("testrig.test_bool", CVarFlags::RUNTIME | CVarFlags::SAVED): bool = true,
@@ -20,6 +56,21 @@ cvar_collection! {
 
         /// Test array cvar
         test_array = cvar TestArray("testrig.test_array", CVarFlags::RUNTIME | CVarFlags::SAVED): Vec<i32> = vec![],
+
+        /// Test struct cvar
+        test_struct = cvar TestStruct("testrig.test_struct", CVarFlags::RUNTIME | CVarFlags::SAVED): TestStructValue = TestStructValue::default(),
+
+        /// Test list of structs cvar
+        test_struct_list = cvar TestStructList("testrig.test_struct_list", CVarFlags::RUNTIME | CVarFlags::SAVED): Vec<TestStructValue> = vec![],
+
+        /// Test map cvar
+        test_map = cvar TestMap("testrig.test_map", CVarFlags::RUNTIME | CVarFlags::SAVED): BTreeMap<String, i32> = BTreeMap::new(),
+
+        /// Test reflection-only cvar
+        test_reflect_only = cvar TestReflectOnly("testrig.test_reflect_only", CVarFlags::RUNTIME | CVarFlags::SAVED): TestReflectOnlyValue = TestReflectOnlyValue::default(),
+
+        /// Test string-convertible cvar
+        test_color = cvar TestColor("testrig.test_color", CVarFlags::RUNTIME | CVarFlags::SAVED): TestColorValue = TestColorValue(0xffffff),
     }
 
     /// Plugin that handles registering all the core CVars.
@@ -27,10 +78,46 @@ cvar_collection! {
     pub struct TestCVarsPlugin;
 }
 
+/// Test derived cvar
+/// with a range.
+#[derive(Reflect, CVar, Clone, PartialEq)]
+#[cvar(path = "testrig.derived.volume", flags = SAVED | RUNTIME, default = 0.5, range = 0.0..=1.0, alias = "testrig.old_volume")]
+pub(crate) struct TestVolume(f32);
+
+/// Test derived cvar with a named field and a constraint.
+#[derive(Reflect, CVar, Default)]
+#[cvar(path = "testrig.derived.name", flags = CVarFlags::RUNTIME, constraint = non_empty, description = "The test rig's name.")]
+pub struct TestName {
+    value: String,
+}
+
+/// Test derived cvar whose value can't be cloned through reflection, with a constraint.
+#[derive(Reflect, CVar, Clone, PartialEq)]
+#[cvar(path = "testrig.derived.tint", flags = RUNTIME, default = TestColorValue(0xffffff), constraint = not_black)]
+pub(crate) struct TestTint(TestColorValue);
+
+fn not_black(value: &TestColorValue) -> Result<(), String> {
+    if value.0 == 0 {
+        Err("Must not be black.".to_owned())
+    } else {
+        Ok(())
+    }
+}
+
+fn non_empty(value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        Err("Must not be empty.".to_owned())
+    } else {
+        Ok(())
+    }
+}
+
 #[must_use]
 pub fn make_test_app() -> App {
     let mut app = App::new();
     app.add_plugins((CVarsPlugin, TestCVarsPlugin));
+    app.register_cvar::<TestVolume>();
+    app.register_cvar::<TestName>();
     app
 }
 
@@ -50,6 +137,23 @@ pub fn read_write_convar_direct() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[test]
+pub fn register_plugins_out_of_order() -> Result<(), Box<dyn Error>> {
+    use crate::WorldExtensions;
+
+    let mut app = App::new();
+    app.add_plugins((TestCVarsPlugin, CVarsPlugin));
+
+    let world = app.world_mut();
+
+    world.set_cvar_reflect(TestInteger::CVAR_PATH, &69)?;
+    world.set_cvar_reflect("core.log_cvar_changes", &true)?;
+
+    assert_eq!(**world.resource::<TestInteger>(), 69);
+
+    Ok(())
+}
+
 #[test]
 pub fn read_write_default_convar_direct() -> Result<(), Box<dyn Error>> {
     let mut app = make_test_app();
@@ -136,6 +240,47 @@ pub fn write_convar_deserialize_wrong_type() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[test]
+pub fn write_convar_from_str() -> Result<(), Box<dyn Error>> {
+    use crate::WorldExtensions;
+
+    let mut app = make_test_app();
+    let world = app.world_mut();
+
+    world.set_cvar_from_str(TestInteger::CVAR_PATH, "37")?;
+    world.set_cvar_from_str(TestColor::CVAR_PATH, "#ff8000")?;
+
+    assert_eq!(**world.resource::<TestInteger>(), 37);
+    assert_eq!(**world.resource::<TestColor>(), TestColorValue(0xff8000));
+    assert!(!world.resource_ref::<TestColor>().is_default());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "parse_cvars")]
+pub fn write_convar_from_str_reflect_only() -> Result<(), Box<dyn Error>> {
+    use crate::WorldExtensions;
+
+    let mut app = make_test_app();
+    let world = app.world_mut();
+
+    world.set_cvar_from_str(
+        TestReflectOnly::CVAR_PATH,
+        r#"{ scale = 2.5, label = "big" }"#,
+    )?;
+
+    assert_eq!(
+        **world.resource::<TestReflectOnly>(),
+        TestReflectOnlyValue {
+            scale: 2.5,
+            label: "big".to_owned()
+        }
+    );
+
+    Ok(())
+}
+
 #[test]
 pub fn write_convar_reflect_wrong_type() -> Result<(), Box<dyn Error>> {
     use crate::WorldExtensions;
@@ -175,6 +320,84 @@ pub fn write_convar_override() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[test]
+pub fn derived_convar_meta() {
+    let app = make_test_app();
+    let world = app.world();
+
+    assert_eq!(**world.resource::<TestVolume>(), 0.5);
+    assert!(world.resource_ref::<TestVolume>().is_default());
+    assert_eq!(TestVolume::DESCRIPTION, "Test derived cvar\nwith a range.");
+    assert_eq!(TestVolume::ALIASES, ["testrig.old_volume"]);
+    assert_eq!(TestVolume::flags(), CVarFlags::SAVED | CVarFlags::RUNTIME);
+
+    assert_eq!(**world.resource::<TestName>(), "");
+    assert_eq!(TestName::DESCRIPTION, "The test rig's name.");
+    assert_eq!(TestName::flags(), CVarFlags::RUNTIME);
+}
+
+#[test]
+pub fn write_derived_convar_constrained() -> Result<(), Box<dyn Error>> {
+    use crate::WorldExtensions;
+
+    let mut app = make_test_app();
+    let world = app.world_mut();
+
+    world.set_cvar_reflect(TestVolume::CVAR_PATH, &0.25f32)?;
+    assert_eq!(**world.resource::<TestVolume>(), 0.25);
+
+    let e = world.set_cvar_reflect(TestVolume::CVAR_PATH, &1.5f32);
+
+    assert!(
+        matches!(e, Err(CVarError::ConstraintViolation(_))),
+        "{} failed to match ConstraintViolation.",
+        e.err().unwrap()
+    );
+    assert_eq!(**world.resource::<TestVolume>(), 0.25);
+
+    world.set_cvar_from_str(TestName::CVAR_PATH, "rig")?;
+    assert_eq!(**world.resource::<TestName>(), "rig");
+
+    let e = world.set_cvar_from_str(TestName::CVAR_PATH, "");
+
+    assert!(
+        matches!(e, Err(CVarError::ConstraintViolation(_))),
+        "{} failed to match ConstraintViolation.",
+        e.err().unwrap()
+    );
+    assert_eq!(**world.resource::<TestName>(), "rig");
+
+    Ok(())
+}
+
+#[test]
+pub fn write_uncloneable_convar_constrained() -> Result<(), Box<dyn Error>> {
+    use crate::WorldExtensions;
+    use bevy_reflect::PartialReflect;
+
+    let mut app = make_test_app();
+    app.register_cvar::<TestTint>();
+
+    let world = app.world_mut();
+
+    // Constraints still apply to values that can't be cloned ahead of time.
+    assert!(TestColorValue(0).reflect_clone().is_err());
+
+    world.set_cvar_from_str(TestTint::CVAR_PATH, "#ff8000")?;
+    assert_eq!(**world.resource::<TestTint>(), TestColorValue(0xff8000));
+
+    let e = world.set_cvar_from_str(TestTint::CVAR_PATH, "#000000");
+
+    assert!(
+        matches!(e, Err(CVarError::ConstraintViolation(_))),
+        "{} failed to match ConstraintViolation.",
+        e.err().unwrap()
+    );
+    assert_eq!(**world.resource::<TestTint>(), TestColorValue(0xff8000));
+
+    Ok(())
+}
+
 #[test]
 #[should_panic(
     expected = "Attempted to insert a duplicate CVar. CVar in question is testrig.test_int"
@@ -182,6 +405,7 @@ pub fn write_convar_override() -> Result<(), Box<dyn Error>> {
 pub fn duplicate_cvar_registration() {
     cvar_collection! {
         /// Collection of test CVars you can use as a system argument.
+        #[allow(dead_code, reason = "Only the plugin is used by tests.")]
         pub struct ErrornousCVars & ErrornousCVarsMut {
             /// Test numeric flag that should cause an error.
             test_integer_shadow = cvar TestInteger("testrig.test_int", CVarFlags::LOCAL): i32 = 69,
@@ -202,6 +426,7 @@ pub fn duplicate_cvar_registration() {
 pub fn mixed_branch_and_leaf_cvar_registration() {
     cvar_collection! {
         /// Collection of test CVars you can use as a system argument.
+        #[allow(dead_code, reason = "Only the plugin is used by tests.")]
         pub struct ErrornousCVars & ErrornousCVarsMut {
             /// Test numeric flag that should cause an error.
             test_integer_branch_shadow = cvar TestInteger("testrig.test_int.shadowed", CVarFlags::LOCAL): i32 = 69,
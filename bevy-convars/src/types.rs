@@ -1,5 +1,5 @@
 use bevy_reflect::Reflect;
-use std::ops;
+use std::{fmt::Display, ops};
 
 /// Flags that can be applied to CVars.
 /// # Remarks
@@ -48,3 +48,33 @@ impl CVarFlags {
         and.0 == other.0
     }
 }
+
+impl Display for CVarFlags {
+    /// Writes the names of the set flags, i.e. `SAVED | RUNTIME`. User flags are written as a single hexadecimal value.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const NAMED: [(CVarFlags, &str); 3] = [
+            (CVarFlags::SAVED, "SAVED"),
+            (CVarFlags::MIRRORED, "MIRRORED"),
+            (CVarFlags::RUNTIME, "RUNTIME"),
+        ];
+
+        let mut names: Vec<String> = NAMED
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| (*name).to_owned())
+            .collect();
+
+        let named = NAMED.iter().fold(0, |acc, (flag, _)| acc | flag.0);
+        let rest = self.0 & !named;
+
+        if rest != 0 {
+            names.push(format!("{rest:#x}"));
+        }
+
+        if names.is_empty() {
+            write!(f, "LOCAL")
+        } else {
+            write!(f, "{}", names.join(" | "))
+        }
+    }
+}